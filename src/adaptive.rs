@@ -0,0 +1,451 @@
+// Joseph Prichard
+// 10/18/2026
+// Adaptive huffman coding, where the encoder and decoder update the same tree after every symbol so no tree is stored
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use crate::block::FileBlock;
+use crate::codec::{Codec, EntryDecoder, EntryEncoder, METHOD_ADAPTIVE};
+use crate::error::Result;
+use crate::read::BitReader;
+use crate::tree::Tree;
+use crate::write::BitWriter;
+
+// every byte, then a symbol marking the end of a stream
+const SYMBOL_COUNT: usize = 257;
+const END_OF_STREAM: u16 = 256;
+// the bit width a symbol is written in the first time it is seen
+const SYMBOL_WIDTH: u8 = 9;
+// the symbol of the leaf standing in for every symbol not yet seen
+const NOT_YET_SEEN: u16 = SYMBOL_COUNT as u16;
+// the leaf index of a symbol not yet seen
+const NO_LEAF: usize = usize::MAX;
+
+// a huffman tree kept optimal for the symbols seen so far, updated with the FGK algorithm
+// nodes are stored in order of decreasing weight, each node's index being its number in the sibling
+// property, so the nodes a leaf can swap with are found next to it rather than by walking the tree
+pub struct AdaptiveTree {
+    tree: Tree,
+    // the index of each symbol's leaf
+    leaves: Vec<usize>,
+    // the index of the leaf for symbols not yet seen
+    not_yet_seen: usize,
+    // the bits of a code from its leaf up, reused between symbols
+    path: Vec<u8>
+}
+
+impl AdaptiveTree {
+    // a tree with only the leaf for symbols not yet seen
+    pub fn new() -> AdaptiveTree {
+        let mut tree = Tree::default();
+        tree.root = tree.add_leaf(NOT_YET_SEEN, 0);
+        AdaptiveTree {
+            tree,
+            leaves: vec![NO_LEAF; SYMBOL_COUNT],
+            not_yet_seen: 0,
+            path: vec![]
+        }
+    }
+
+    // writes the symbol's code, or the code for unseen symbols and then the symbol itself
+    pub fn encode_symbol<W: Write + ?Sized>(&mut self, writer: &mut BitWriter<W>, symbol: u16) -> io::Result<()> {
+        let leaf = self.leaves[symbol as usize];
+        if leaf == NO_LEAF {
+            self.write_code(writer, self.not_yet_seen)?;
+            writer.write_bits_u32(symbol as u32, SYMBOL_WIDTH)?;
+        } else {
+            self.write_code(writer, leaf)?;
+        }
+        self.update(symbol);
+        Ok(())
+    }
+
+    pub fn decode_symbol<R: Read + ?Sized>(&mut self, reader: &mut BitReader<R>) -> io::Result<u16> {
+        let mut index = self.tree.root;
+        while let (Some(left), Some(right)) = (self.tree.nodes[index].left, self.tree.nodes[index].right) {
+            index = if reader.read_bit()? > 0 { right } else { left };
+        }
+        let symbol = if index == self.not_yet_seen {
+            let symbol = reader.read_bits_u32(SYMBOL_WIDTH)? as usize;
+            if symbol >= SYMBOL_COUNT || self.leaves[symbol] != NO_LEAF {
+                return Err(io::Error::new(ErrorKind::InvalidData, "New symbol was already seen or is out of range"));
+            }
+            symbol as u16
+        } else {
+            self.tree.nodes[index].plain_symbol
+        };
+        self.update(symbol);
+        Ok(symbol)
+    }
+
+    // the codes are the path from the root, so they are found from the leaf up and written in reverse
+    fn write_code<W: Write + ?Sized>(&mut self, writer: &mut BitWriter<W>, leaf: usize) -> io::Result<()> {
+        self.path.clear();
+        let mut index = leaf;
+        while let Some(parent) = self.tree.nodes[index].parent {
+            self.path.push((self.tree.nodes[parent].right == Some(index)) as u8);
+            index = parent;
+        }
+        for &bit in self.path.iter().rev() {
+            writer.write_bit(bit)?;
+        }
+        Ok(())
+    }
+
+    fn update(&mut self, symbol: u16) {
+        let mut index = self.leaves[symbol as usize];
+        if index == NO_LEAF {
+            index = self.add_leaf(symbol);
+        }
+        loop {
+            // move the node past every other node of its weight, keeping the weights in order once it is incremented
+            let leader = self.block_leader(index);
+            if leader != index && Some(leader) != self.tree.nodes[index].parent {
+                self.swap(index, leader);
+                index = leader;
+            }
+            self.tree.nodes[index].weight += 1;
+            match self.tree.nodes[index].parent {
+                Some(parent) => index = parent,
+                None => break
+            }
+        }
+    }
+
+    // splits the leaf for unseen symbols into a leaf for the symbol and a new leaf for unseen symbols
+    fn add_leaf(&mut self, symbol: u16) -> usize {
+        let parent = self.not_yet_seen;
+        let leaf = self.tree.add_leaf(symbol, 0);
+        self.not_yet_seen = self.tree.add_leaf(NOT_YET_SEEN, 0);
+        self.tree.set_children(parent, self.not_yet_seen, leaf);
+        self.tree.nodes[parent].plain_symbol = 0;
+        self.leaves[symbol as usize] = leaf;
+        leaf
+    }
+
+    // the lowest numbered node with the same weight
+    fn block_leader(&self, index: usize) -> usize {
+        let weight = self.tree.nodes[index].weight;
+        let mut leader = index;
+        while leader > 0 && self.tree.nodes[leader - 1].weight == weight {
+            leader -= 1;
+        }
+        leader
+    }
+
+    // swaps two subtrees, then points the leaves that moved at their new indices
+    fn swap(&mut self, a: usize, b: usize) {
+        self.tree.swap_subtrees(a, b);
+        for index in [a, b] {
+            let node = &self.tree.nodes[index];
+            if !node.is_leaf() {
+                continue;
+            }
+            if node.plain_symbol == NOT_YET_SEEN {
+                self.not_yet_seen = index;
+            } else {
+                self.leaves[node.plain_symbol as usize] = index;
+            }
+        }
+    }
+}
+
+impl Default for AdaptiveTree {
+    fn default() -> AdaptiveTree {
+        AdaptiveTree::new()
+    }
+}
+
+// adaptive huffman coding for archive entries, where the entry's size ends its data rather than a symbol
+pub struct AdaptiveCodec;
+
+impl Codec for AdaptiveCodec {
+    fn method(&self) -> u8 {
+        METHOD_ADAPTIVE
+    }
+
+    fn name(&self) -> &'static str {
+        "adaptive"
+    }
+
+    fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>> {
+        // the archive needs the size of the data up front, so the first pass encodes into nothing
+        let mut writer = BitWriter::new(io::sink());
+        let byte_size = encode_entry(input, &mut writer)?;
+        Ok(Box::new(AdaptiveEntryEncoder { data_bit_size: writer.write_len(), byte_size }))
+    }
+
+    fn create_decoder(&self, _reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>> {
+        Ok(Box::new(AdaptiveEntryDecoder {
+            tree: AdaptiveTree::new(),
+            remaining: block.original_byte_size
+        }))
+    }
+}
+
+// encodes each byte of the input, returning the number of bytes
+fn encode_entry<W: Write + ?Sized>(input: &mut dyn Read, writer: &mut BitWriter<W>) -> io::Result<u64> {
+    let mut tree = AdaptiveTree::new();
+    let mut reader = BitReader::new(input);
+    let mut byte_size = 0;
+    while !reader.eof()? {
+        tree.encode_symbol(writer, reader.read_byte()? as u16)?;
+        byte_size += 1;
+    }
+    Ok(byte_size)
+}
+
+struct AdaptiveEntryEncoder {
+    data_bit_size: u64,
+    // the byte size of the file when its size was measured
+    byte_size: u64
+}
+
+impl EntryEncoder for AdaptiveEntryEncoder {
+    fn header_bit_size(&self) -> u64 {
+        0
+    }
+
+    fn data_bit_size(&self) -> Option<u64> {
+        Some(self.data_bit_size)
+    }
+
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()> {
+        // stop at the size that was measured in case the file has grown since
+        encode_entry(&mut input.take(self.byte_size), writer)?;
+        Ok(())
+    }
+}
+
+struct AdaptiveEntryDecoder {
+    tree: AdaptiveTree,
+    // the number of bytes left to decode
+    remaining: u64
+}
+
+impl EntryDecoder for AdaptiveEntryDecoder {
+    fn decode(&mut self, reader: &mut BitReader<dyn Read + '_>, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() && self.remaining > 0 {
+            let symbol = self.tree.decode_symbol(reader)?;
+            if symbol == END_OF_STREAM {
+                return Err(io::Error::new(ErrorKind::InvalidData, "Entry ended before its size"));
+            }
+            buf[len] = symbol as u8;
+            self.remaining -= 1;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+// compresses everything written into it in a single pass, for streams whose length isn't known
+// the stream ends with an end of stream symbol, written by finish
+pub struct AdaptiveEncoder<W: Write> {
+    writer: BitWriter<W>,
+    tree: AdaptiveTree,
+    finished: bool
+}
+
+impl<W: Write> AdaptiveEncoder<W> {
+    pub fn new(inner: W) -> AdaptiveEncoder<W> {
+        AdaptiveEncoder {
+            writer: BitWriter::new(inner),
+            tree: AdaptiveTree::new(),
+            finished: false
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer.get_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        self.writer.get_mut()
+    }
+
+    // ends the stream, nothing more can be written after
+    pub fn finish(mut self) -> io::Result<()> {
+        self.end_stream()
+    }
+
+    fn end_stream(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.finished = true;
+            self.tree.encode_symbol(&mut self.writer, END_OF_STREAM)?;
+            self.writer.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for AdaptiveEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.finished {
+            return Err(io::Error::new(ErrorKind::BrokenPipe, "Stream has already ended"));
+        }
+        for &byte in buf {
+            self.tree.encode_symbol(&mut self.writer, byte as u16)?;
+        }
+        Ok(buf.len())
+    }
+
+    // passes on every whole byte, the bits of a partly written byte wait for the next symbols
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush_whole_bytes()
+    }
+}
+
+impl<W: Write> Drop for AdaptiveEncoder<W> {
+    fn drop(&mut self) {
+        // errors can't be reported from drop, callers should finish to observe them
+        let _ = self.end_stream();
+    }
+}
+
+// decompresses a stream written by an adaptive encoder, up to its end of stream symbol
+pub struct AdaptiveDecoder<R: Read> {
+    reader: BitReader<R>,
+    tree: AdaptiveTree,
+    ended: bool
+}
+
+impl<R: Read> AdaptiveDecoder<R> {
+    pub fn new(inner: R) -> AdaptiveDecoder<R> {
+        AdaptiveDecoder {
+            reader: BitReader::new(inner),
+            tree: AdaptiveTree::new(),
+            ended: false
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.reader.get_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        self.reader.get_mut()
+    }
+}
+
+impl<R: Read> Read for AdaptiveDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() && !self.ended {
+            let symbol = self.tree.decode_symbol(&mut self.reader)?;
+            if symbol == END_OF_STREAM {
+                self.ended = true;
+            } else {
+                buf[len] = symbol as u8;
+                len += 1;
+            }
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::io::Cursor;
+    use crate::codec::METHOD_ADAPTIVE;
+    use crate::compress::tests::{noise, temp_dir};
+    use crate::compress::ArchiveWriter;
+    use crate::decompress::ArchiveReader;
+    use super::*;
+
+    // checks every node's links and weights, and that the weights never increase along the node order
+    fn check_sibling_property(tree: &AdaptiveTree) {
+        let nodes = &tree.tree.nodes;
+        for (index, node) in nodes.iter().enumerate() {
+            if index > 0 {
+                assert!(nodes[index - 1].weight >= node.weight, "node {} outweighs the node before it", index);
+            }
+            if let (Some(left), Some(right)) = (node.left, node.right) {
+                assert_eq!((nodes[left].parent, nodes[right].parent), (Some(index), Some(index)));
+                assert_eq!(node.weight, nodes[left].weight + nodes[right].weight);
+                // siblings are numbered next to each other
+                assert_eq!(left.abs_diff(right), 1);
+            } else if node.plain_symbol == NOT_YET_SEEN {
+                assert_eq!(tree.not_yet_seen, index);
+            } else {
+                assert_eq!(tree.leaves[node.plain_symbol as usize], index);
+            }
+        }
+    }
+
+    #[test]
+    fn updates_keep_the_sibling_property() {
+        let mut tree = AdaptiveTree::new();
+        let mut writer = BitWriter::new(io::sink());
+        for byte in b"abracadabra".iter().chain(&noise(2000)) {
+            tree.encode_symbol(&mut writer, *byte as u16).unwrap();
+            check_sibling_property(&tree);
+        }
+    }
+
+    #[test]
+    fn codes_follow_the_symbols_seen_so_far() {
+        let mut tree = AdaptiveTree::new();
+        let mut writer = BitWriter::new(io::sink());
+        // the first symbol has no code yet, so only the symbol itself is written
+        tree.encode_symbol(&mut writer, b'a' as u16).unwrap();
+        assert_eq!(writer.write_len(), SYMBOL_WIDTH as u64);
+        for _ in 0..100 {
+            tree.encode_symbol(&mut writer, b'a' as u16).unwrap();
+        }
+        tree.encode_symbol(&mut writer, b'b' as u16).unwrap();
+        // once common, a symbol takes a single bit
+        let start = writer.write_len();
+        tree.encode_symbol(&mut writer, b'a' as u16).unwrap();
+        assert_eq!(writer.write_len() - start, 1);
+    }
+
+    #[test]
+    fn streams_round_trip() {
+        for input in [vec![], vec![b'a'], b"abracadabra".repeat(50), noise(10_000)] {
+            let mut encoded = vec![];
+            let mut encoder = AdaptiveEncoder::new(&mut encoded);
+            encoder.write_all(&input).unwrap();
+            encoder.finish().unwrap();
+            let mut decoded = vec![];
+            AdaptiveDecoder::new(encoded.as_slice()).read_to_end(&mut decoded).unwrap();
+            assert_eq!(decoded, input);
+        }
+    }
+
+    #[test]
+    fn symbols_seen_twice_as_new_are_refused() {
+        // 'a' as a new symbol, then the code for unseen symbols followed by 'a' again
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_bits_u32(b'a' as u32, SYMBOL_WIDTH).unwrap();
+        writer.write_bit(0).unwrap();
+        writer.write_bits_u32(b'a' as u32, SYMBOL_WIDTH).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let mut decoded = vec![];
+        let err = AdaptiveDecoder::new(bytes.as_slice()).read_to_end(&mut decoded).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn entries_end_at_their_size() {
+        let dir = temp_dir("adaptive");
+        let contents = b"mostly the same few words ".repeat(100);
+        fs::write(dir.join("text.txt"), &contents).unwrap();
+        let mut archive = vec![];
+        let mut writer = ArchiveWriter::new(&mut archive);
+        writer.set_method(METHOD_ADAPTIVE).unwrap();
+        writer.add_entry(&dir).unwrap();
+        writer.finish().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut reader = ArchiveReader::new(Cursor::new(archive)).unwrap();
+        let index = reader.entries().iter().position(|block| block.filename_rel.ends_with(b"text.txt")).unwrap();
+        assert_eq!((reader.entries()[index].method, reader.entries()[index].tree_bit_size), (METHOD_ADAPTIVE, 0));
+        let mut decoded = vec![];
+        reader.by_index(index).unwrap().read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, contents);
+    }
+}
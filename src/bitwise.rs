@@ -2,9 +2,9 @@
 // 1/5/2023
 // Bytewise and bitwise utilities for symbols to be compressed
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Default)]
 pub struct SymbolCode {
-    pub plain_symbol: u8,
+    pub plain_symbol: u16,
     pub encoded_symbol: u32,
     pub bit_len: u8
 }
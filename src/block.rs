@@ -2,22 +2,82 @@
 // 1/5/2023
 // Type definitions and utilities for blocks in an archive
 
-use crate::bitwise::SymbolCode;
-use crate::tree::Tree;
-use crate::utils::get_size_of;
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use crate::codec::{method_name, EntryEncoder, METHOD_STORED};
+use crate::charset::{SIG, VERSION_MAJOR, VERSION_MINOR};
+use crate::utils::{bytes_to_path, format_timestamp, get_size_of};
 
-pub struct CodeBook {
-    pub symbol_table: Vec<SymbolCode>,
-    pub tree: Tree
+// the start of an archive, describing the format the rest of the archive is written in
+pub struct ArchiveHeader {
+    // version of the format the archive was written with
+    pub version_major: u16,
+    pub version_minor: u16,
+    // bitfield of features used by the archive
+    pub flags: u32
+}
+
+impl ArchiveHeader {
+    pub fn new() -> ArchiveHeader {
+        ArchiveHeader {
+            version_major: VERSION_MAJOR,
+            version_minor: VERSION_MINOR,
+            flags: 0
+        }
+    }
+
+    pub fn get_header_size(&self) -> u64 {
+        (get_size_of(SIG) +
+            get_size_of(self.version_major) +
+            get_size_of(self.version_minor) +
+            get_size_of(self.flags)
+        ) as u64
+    }
+}
+
+impl Default for ArchiveHeader {
+    fn default() -> ArchiveHeader {
+        ArchiveHeader::new()
+    }
+}
+
+// the kind of file system entry a block stores
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum EntryType {
+    File = 0,
+    Directory = 1,
+    // stores the path the link points to
+    Symlink = 2,
+    // stores the relative name of an earlier entry for the same file
+    HardLink = 3
+}
+
+impl EntryType {
+    pub fn from_u8(byte: u8) -> Option<EntryType> {
+        match byte {
+            0 => Some(EntryType::File),
+            1 => Some(EntryType::Directory),
+            2 => Some(EntryType::Symlink),
+            3 => Some(EntryType::HardLink),
+            _ => None
+        }
+    }
 }
 
 // a part of a compressed archive
 pub struct FileBlock {
     // full name of file including path
-    pub filename_abs: String,
-    // relative name of file to base directory in archive
-    pub filename_rel: String,
-    // length of encoded tree structure in bits
+    pub filename_abs: PathBuf,
+    // relative name of file to base directory in archive, stored as raw path bytes
+    pub filename_rel: Vec<u8>,
+    // whether the block is a file with compressed data or a directory or link without any
+    pub entry_type: EntryType,
+    // target of a symlink, relative name of a hard link's earlier entry, or relative name of the earlier file a
+    // deduplicated file shares its compressed data with, empty otherwise
+    pub link_target: Vec<u8>,
+    // id of the codec the file's contents are stored with, directories and links are stored with no contents
+    pub method: u8,
+    // length of whatever the codec writes ahead of the compressed data, such as a code table, in bits
     pub tree_bit_size: u64,
     // length of compressed data in bits
     pub data_bit_size: u64,
@@ -25,53 +85,190 @@ pub struct FileBlock {
     pub file_byte_offset: u64,
     // original file size
     pub original_byte_size: u64,
-    // code book for compressing the file to the archive
-    // a code book is optional because it isn't present in the block until created
-    pub code_book: Option<CodeBook>
+    // crc32 of the original file contents
+    pub checksum: u32,
+    // unix permission bits of the original file, zero when they aren't known
+    pub mode: u32,
+    // modification time of the original file since the unix epoch
+    pub mtime: i64,
+    pub mtime_nsec: u32,
+    // unix owner and group of the original file
+    pub uid: u32,
+    pub gid: u32,
+    // bit position of the compressed data in the byte at the file offset, only entries in a solid stream start mid byte
+    pub bit_offset: u8,
+    // index of the solid stream's code table the entry is coded with, zero unless the archive has several
+    pub code_book: u16,
+    // encoder for compressing the file to the archive
+    // an encoder is optional because it isn't present in the block until created
+    pub encoder: Option<Box<dyn EntryEncoder>>
 }
 
 impl FileBlock {
-    pub fn new(filename_rel: &str, filename_abs: &str) -> FileBlock {
+    pub fn new(filename_rel: &[u8], filename_abs: &Path) -> FileBlock {
         FileBlock {
-            filename_abs: String::from(filename_abs),
-            filename_rel: String::from(filename_rel),
+            filename_abs: filename_abs.to_path_buf(),
+            filename_rel: filename_rel.to_vec(),
+            entry_type: EntryType::File,
+            link_target: vec![],
+            method: METHOD_STORED,
             tree_bit_size: 0,
             data_bit_size: 0,
             file_byte_offset: 0,
             original_byte_size: 0,
-            code_book: None
+            checksum: 0,
+            mode: 0,
+            mtime: 0,
+            mtime_nsec: 0,
+            uid: 0,
+            gid: 0,
+            bit_offset: 0,
+            code_book: 0,
+            encoder: None
+        }
+    }
+
+    pub fn filename_rel_path(&self) -> PathBuf {
+        bytes_to_path(&self.filename_rel)
+    }
+
+    // relative name of the file for printing, with any invalid unicode replaced
+    pub fn display_name(&self) -> Cow<'_, str> {
+        String::from_utf8_lossy(&self.filename_rel)
+    }
+
+    pub fn link_target_path(&self) -> PathBuf {
+        bytes_to_path(&self.link_target)
+    }
+
+    // whether the file has the same contents as an earlier file, and points at that file's compressed data
+    pub fn is_duplicate(&self) -> bool {
+        self.entry_type == EntryType::File && !self.link_target.is_empty()
+    }
+
+    // relative name for printing, marking directories and showing where links point
+    pub fn display_entry(&self) -> String {
+        let name = self.display_name();
+        let target = String::from_utf8_lossy(&self.link_target);
+        match self.entry_type {
+            EntryType::File if self.is_duplicate() => format!("{} == {}", name, target),
+            EntryType::File => name.into_owned(),
+            EntryType::Directory => format!("{}/", name),
+            EntryType::Symlink => format!("{} -> {}", name, target),
+            EntryType::HardLink => format!("{} => {}", name, target)
         }
     }
 
     pub fn get_header_size(&self) -> u64 {
-        // string len calculation includes null terminator
-        (self.filename_rel.as_bytes().len() + 1 +
+        // the record, name, and link target are each prefixed by their length
+        (get_size_of(0u32) +
+            get_size_of(0u32) +
+            self.filename_rel.len() +
             get_size_of(self.tree_bit_size) +
             get_size_of(self.data_bit_size) +
             get_size_of(self.file_byte_offset) +
-            get_size_of(self.original_byte_size)
+            get_size_of(self.original_byte_size) +
+            get_size_of(self.checksum) +
+            get_size_of(self.mode) +
+            get_size_of(self.mtime) +
+            get_size_of(self.mtime_nsec) +
+            get_size_of(self.uid) +
+            get_size_of(self.gid) +
+            get_size_of(self.entry_type as u8) +
+            get_size_of(0u32) +
+            self.link_target.len() +
+            get_size_of(self.method) +
+            get_size_of(self.bit_offset) +
+            get_size_of(self.code_book)
         ) as u64
     }
 }
 
-pub fn list_file_blocks(blocks: &Vec<FileBlock>) {
+pub fn list_file_blocks(blocks: &[FileBlock]) {
     println!(
-        "{:>15}\t\t{:>15}\t\t{:>8}\t\t{:25}",
+        "{:>15}\t\t{:>15}\t\t{:>8}\t\t{:>8}\t\t{:25}",
         "compressed",
         "uncompressed",
         "ratio",
+        "method",
         "uncompressed_name"
     );
     for block in blocks {
-        let total_byte_size = (block.data_bit_size + block.tree_bit_size) / 8;
+        // duplicates share the data of the file they duplicate, so they take no space of their own
+        // the sizes of a corrupted archive can be anything, so they are summed without overflowing
+        let total_byte_size = if block.is_duplicate() { 0 } else { block.data_bit_size.saturating_add(block.tree_bit_size) / 8 };
+        // only files have compressed data to compare against, and empty files have no ratio
+        let (ratio, method) = match block.entry_type {
+            EntryType::File if block.is_duplicate() => (String::from("-"), String::from("dedup")),
+            EntryType::File if block.original_byte_size == 0 => (String::from("-"), method_name(block.method)),
+            EntryType::File => (
+                format!("{:.2}%", (total_byte_size as f64) / (block.original_byte_size as f64) * 100.0),
+                method_name(block.method)
+            ),
+            _ => (String::from("-"), String::from("-"))
+        };
         println!(
-            "{:>15}\t\t{:>15}\t\t{:>8}\t\t{:25}",
+            "{:>15}\t\t{:>15}\t\t{:>8}\t\t{:>8}\t\t{:25}",
             total_byte_size,
             block.original_byte_size,
-            format!("{:.2}%", (total_byte_size as f64) / (block.original_byte_size as f64) * 100.0),
-            block.filename_rel
+            ratio,
+            method,
+            block.display_entry()
+        );
+    }
+    println!();
+}
+
+// compares the entries that store their huffman coded size against it, with the size saved as a share of it
+pub fn list_huffman_gains(blocks: &[FileBlock], huffman_bit_sizes: &[Option<u64>]) {
+    println!(
+        "{:>15}\t\t{:>15}\t\t{:>8}\t\t{:25}",
+        "compressed",
+        "huffman",
+        "gain",
+        "uncompressed_name"
+    );
+    for (block, &huffman_bit_size) in blocks.iter().zip(huffman_bit_sizes) {
+        // duplicates would only repeat the gain of the file they duplicate
+        let Some(huffman_bit_size) = huffman_bit_size.filter(|_| !block.is_duplicate()) else {
+            continue;
+        };
+        let total_byte_size = block.data_bit_size.saturating_add(block.tree_bit_size) / 8;
+        let huffman_byte_size = huffman_bit_size.div_ceil(8);
+        let gain = if huffman_byte_size == 0 {
+            String::from("-")
+        } else {
+            format!("{:.2}%", (huffman_byte_size as f64 - total_byte_size as f64) / (huffman_byte_size as f64) * 100.0)
+        };
+        println!(
+            "{:>15}\t\t{:>15}\t\t{:>8}\t\t{:25}",
+            total_byte_size,
+            huffman_byte_size,
+            gain,
+            block.display_entry()
         );
     }
     println!();
 }
 
+pub fn list_file_metadata(blocks: &[FileBlock]) {
+    println!(
+        "{:>8}\t\t{:>8}\t\t{:>8}\t\t{:>19}\t\t{:25}",
+        "mode",
+        "uid",
+        "gid",
+        "modified",
+        "uncompressed_name"
+    );
+    for block in blocks {
+        println!(
+            "{:>8}\t\t{:>8}\t\t{:>8}\t\t{:>19}\t\t{:25}",
+            format!("{:o}", block.mode & 0o7777),
+            block.uid,
+            block.gid,
+            format_timestamp(block.mtime),
+            block.display_entry()
+        );
+    }
+    println!();
+}
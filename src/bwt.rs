@@ -0,0 +1,469 @@
+// Joseph Prichard
+// 10/18/2026
+// Block sorting codec, a burrows-wheeler transform, move-to-front, and run-length coding of zeros ahead of huffman coding
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use crate::bitwise::SymbolCode;
+use crate::block::FileBlock;
+use crate::canonical::{DecodeTable, MAX_CODE_LEN};
+use crate::codec::{Codec, EntryDecoder, EntryEncoder, METHOD_BWT};
+use crate::error::{Result, ZipperError};
+use crate::huffman;
+use crate::huffman::{tree_error, DEFAULT_MAX_CODE_LEN};
+use crate::read::BitReader;
+use crate::write::BitWriter;
+
+// the byte size of each block unless a different size is set
+pub const DEFAULT_SORT_BLOCK_SIZE: u32 = 1 << 20;
+// runs of zeros are written in bijective base 2 with these two digits, and every other value is moved up past them
+const RUN_A: u16 = 0;
+const RUN_B: u16 = 1;
+const SYMBOL_COUNT: usize = 257;
+
+pub struct BwtCodec {
+    // the number of bytes of the original file sorted together, the last block holds whatever is left
+    block_size: u32,
+    // the longest code any symbol is given
+    max_code_len: u8
+}
+
+impl BwtCodec {
+    pub fn new(block_size: u32, max_code_len: u8) -> BwtCodec {
+        BwtCodec {
+            block_size: block_size.max(1),
+            max_code_len: max_code_len.min(MAX_CODE_LEN)
+        }
+    }
+}
+
+impl Default for BwtCodec {
+    fn default() -> BwtCodec {
+        BwtCodec::new(DEFAULT_SORT_BLOCK_SIZE, DEFAULT_MAX_CODE_LEN)
+    }
+}
+
+impl Codec for BwtCodec {
+    fn method(&self) -> u8 {
+        METHOD_BWT
+    }
+
+    fn name(&self) -> &'static str {
+        "bwt"
+    }
+
+    fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>> {
+        let mut blocks = vec![];
+        let mut block = vec![];
+        loop {
+            block.clear();
+            (&mut *input).take(self.block_size as u64).read_to_end(&mut block)?;
+            if block.is_empty() {
+                break;
+            }
+            let (_, symbols) = transform(&block);
+            let mut freq_table = vec![0u64; SYMBOL_COUNT];
+            for &symbol in &symbols {
+                freq_table[symbol as usize] += 1;
+            }
+            let tree = huffman::create_code_tree(&freq_table, self.max_code_len);
+            let symbol_table = huffman::create_code_table(&tree, SYMBOL_COUNT);
+            // each block starts on a byte so it can be jumped to
+            let mut bit_size = 32 + huffman::code_lengths_bit_size(&symbol_table);
+            for (freq, symbol_code) in freq_table.iter().zip(&symbol_table) {
+                bit_size += freq * (symbol_code.bit_len as u64);
+            }
+            blocks.push(Block { symbol_table, byte_size: block.len() as u64, compressed_byte_size: bit_size.div_ceil(8) });
+        }
+        Ok(Box::new(BwtEncoder { block_size: self.block_size, blocks }))
+    }
+
+    fn create_decoder(&self, reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>> {
+        let block_size = reader.read_u32().map_err(tree_error)?;
+        if block_size == 0 {
+            return Err(ZipperError::MalformedTree);
+        }
+        // the index holds where each block starts, counted in bytes from the end of the index
+        let block_count = block.original_byte_size.div_ceil(block_size as u64);
+        if block_count != block.tree_bit_size.saturating_sub(32) / 64 {
+            return Err(ZipperError::MalformedTree);
+        }
+        // the offsets are kept in bits, and every block has to end within the entry's data
+        let mut block_bit_offsets = vec![];
+        let mut block_offset: u64 = 0;
+        for _ in 0..block_count {
+            let block_bit_offset = block_offset.checked_mul(8).ok_or(ZipperError::MalformedTree)?;
+            block_bit_offsets.push(block_bit_offset);
+            block_offset = block_offset
+                .checked_add(reader.read_u64().map_err(tree_error)?)
+                .ok_or(ZipperError::MalformedTree)?;
+        }
+        if block_offset.checked_mul(8).is_none_or(|bit_size| bit_size > block.data_bit_size) {
+            return Err(ZipperError::MalformedTree);
+        }
+        Ok(Box::new(BwtDecoder {
+            block_size: block_size as u64,
+            block_bit_offsets,
+            buffer: vec![],
+            buffer_position: 0,
+            position: 0,
+            byte_size: block.original_byte_size
+        }))
+    }
+}
+
+// sorts the block and codes the sorted bytes as symbols for the huffman coder, returning the sorted row of the block itself
+fn transform(block: &[u8]) -> (u32, Vec<u16>) {
+    let (primary, last_bytes) = burrows_wheeler(block);
+    (primary, run_length_zeros(&move_to_front(&last_bytes)))
+}
+
+// the byte before each suffix of the block in sorted order, leaving out the row for the whole block, which is returned
+// the block is sorted with a sentinel after it that is smaller than every byte
+fn burrows_wheeler(block: &[u8]) -> (u32, Vec<u8>) {
+    let mut primary = 0;
+    let mut last_bytes = Vec::with_capacity(block.len());
+    for (row, &suffix) in suffix_array(block).iter().enumerate() {
+        if suffix == 0 {
+            primary = row as u32;
+        } else {
+            last_bytes.push(block[suffix as usize - 1]);
+        }
+    }
+    (primary, last_bytes)
+}
+
+// sorts the suffixes of the block and its sentinel by prefix doubling: suffixes sorted by their first k bytes are
+// sorted by their first 2k bytes using the rank of the suffix k bytes later, until every suffix has its own rank
+fn suffix_array(block: &[u8]) -> Vec<u32> {
+    let len = block.len() + 1;
+    let mut ranks: Vec<u32> = block.iter().map(|&byte| byte as u32 + 1).chain([0]).collect();
+    let mut rank_count = 257;
+    let mut suffixes: Vec<u32> = (0..len as u32).collect();
+    let mut by_next_rank = vec![0u32; len];
+    let mut next_ranks = vec![0u32; len];
+    counting_sort(&(0..len as u32).collect::<Vec<_>>(), &ranks, rank_count, &mut suffixes);
+
+    let mut k = 1;
+    loop {
+        // suffixes too short to have a suffix k bytes later sort first, then the rest in order of that later suffix
+        let mut count = 0;
+        for suffix in len - k.min(len)..len {
+            by_next_rank[count] = suffix as u32;
+            count += 1;
+        }
+        for &suffix in &suffixes {
+            if suffix as usize >= k {
+                by_next_rank[count] = suffix - k as u32;
+                count += 1;
+            }
+        }
+        counting_sort(&by_next_rank, &ranks, rank_count, &mut suffixes);
+
+        let next_rank = |suffix: u32| ranks.get(suffix as usize + k).map_or(0, |&rank| rank + 1);
+        next_ranks[suffixes[0] as usize] = 0;
+        for i in 1..len {
+            let (prev, suffix) = (suffixes[i - 1], suffixes[i]);
+            let same = ranks[prev as usize] == ranks[suffix as usize] && next_rank(prev) == next_rank(suffix);
+            next_ranks[suffix as usize] = next_ranks[prev as usize] + !same as u32;
+        }
+        std::mem::swap(&mut ranks, &mut next_ranks);
+        rank_count = ranks[suffixes[len - 1] as usize] as usize + 1;
+        if rank_count == len {
+            return suffixes;
+        }
+        k *= 2;
+    }
+}
+
+// stably sorts the suffixes by their rank
+fn counting_sort(suffixes: &[u32], ranks: &[u32], rank_count: usize, sorted: &mut [u32]) {
+    let mut starts = vec![0usize; rank_count + 1];
+    for &suffix in suffixes {
+        starts[ranks[suffix as usize] as usize + 1] += 1;
+    }
+    for rank in 1..=rank_count {
+        starts[rank] += starts[rank - 1];
+    }
+    for &suffix in suffixes {
+        let rank = ranks[suffix as usize] as usize;
+        sorted[starts[rank]] = suffix;
+        starts[rank] += 1;
+    }
+}
+
+// replaces each byte with its position in a list of bytes ordered by how recently they were seen
+fn move_to_front(bytes: &[u8]) -> Vec<u8> {
+    let mut recent: Vec<u8> = (0..=255).collect();
+    bytes.iter().map(|&byte| {
+        let position = recent.iter().position(|&recent_byte| recent_byte == byte).expect("Every byte is in the list");
+        recent.copy_within(0..position, 1);
+        recent[0] = byte;
+        position as u8
+    }).collect()
+}
+
+// writes each run of zeros as its length in bijective base 2, least significant digit first
+fn run_length_zeros(values: &[u8]) -> Vec<u16> {
+    let mut symbols = vec![];
+    let mut run = 0;
+    for &value in values {
+        if value == 0 {
+            run += 1;
+        } else {
+            push_run(&mut symbols, run);
+            run = 0;
+            symbols.push(value as u16 + 1);
+        }
+    }
+    push_run(&mut symbols, run);
+    symbols
+}
+
+fn push_run(symbols: &mut Vec<u16>, mut run: u64) {
+    while run > 0 {
+        run -= 1;
+        symbols.push(if run & 1 == 0 { RUN_A } else { RUN_B });
+        run >>= 1;
+    }
+}
+
+struct Block {
+    symbol_table: Vec<SymbolCode>,
+    // the byte size of the original bytes in the block
+    byte_size: u64,
+    // the byte size of the block's sorted row, code table, and compressed data
+    compressed_byte_size: u64
+}
+
+struct BwtEncoder {
+    block_size: u32,
+    blocks: Vec<Block>
+}
+
+impl EntryEncoder for BwtEncoder {
+    // the block size then the compressed size of every block
+    fn header_bit_size(&self) -> u64 {
+        32 + 64 * self.blocks.len() as u64
+    }
+
+    fn data_bit_size(&self) -> Option<u64> {
+        Some(self.blocks.iter().map(|block| block.compressed_byte_size * 8).sum())
+    }
+
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()> {
+        writer.write_u32(self.block_size)?;
+        for block in &self.blocks {
+            writer.write_u64(block.compressed_byte_size)?;
+        }
+        // the blocks are sorted again rather than kept from the first pass
+        let mut bytes = vec![];
+        for block in &self.blocks {
+            bytes.clear();
+            (&mut *input).take(block.byte_size).read_to_end(&mut bytes)?;
+            let (primary, symbols) = transform(&bytes);
+            writer.write_u32(primary)?;
+            huffman::write_code_lengths(writer, &block.symbol_table)?;
+            for symbol in symbols {
+                writer.write_symbol(&block.symbol_table[symbol as usize])?;
+            }
+            writer.align_to_byte();
+        }
+        Ok(())
+    }
+}
+
+struct BwtDecoder {
+    block_size: u64,
+    // the bit offset of each block from the end of the index
+    block_bit_offsets: Vec<u64>,
+    // the decoded bytes of the current block
+    buffer: Vec<u8>,
+    buffer_position: usize,
+    // the number of bytes of the entry decoded so far
+    position: u64,
+    byte_size: u64
+}
+
+impl BwtDecoder {
+    fn decode_block<R: Read + ?Sized>(&mut self, reader: &mut BitReader<R>) -> Result<()> {
+        let byte_size = self.block_size.min(self.byte_size - self.position) as usize;
+        reader.align_to_byte();
+        let primary = reader.read_u32().map_err(tree_error)? as usize;
+        if primary > byte_size {
+            return Err(ZipperError::MalformedTree);
+        }
+        let table = huffman::read_code_lengths(reader, SYMBOL_COUNT)?;
+        let values = read_values(reader, &table, byte_size)?;
+        let last_bytes = undo_move_to_front(&values);
+        self.buffer = undo_burrows_wheeler(&last_bytes, primary)?;
+        self.buffer_position = 0;
+        Ok(())
+    }
+}
+
+// reads symbols until they make up the block's number of move-to-front values
+fn read_values<R: Read + ?Sized>(reader: &mut BitReader<R>, table: &DecodeTable, byte_size: usize) -> io::Result<Vec<u8>> {
+    let mut values = Vec::with_capacity(byte_size);
+    let mut run = 0;
+    let mut digit_weight = 1;
+    while values.len() + run < byte_size {
+        let symbol = table.decode_next_symbol(reader)?;
+        if symbol == RUN_A || symbol == RUN_B {
+            run += (symbol as usize + 1) * digit_weight;
+            digit_weight *= 2;
+            if values.len() + run > byte_size {
+                return Err(io::Error::new(ErrorKind::InvalidData, "Run of zeros is longer than the block"));
+            }
+        } else {
+            values.resize(values.len() + run, 0);
+            run = 0;
+            digit_weight = 1;
+            values.push((symbol - 1) as u8);
+        }
+    }
+    values.resize(byte_size, 0);
+    Ok(values)
+}
+
+fn undo_move_to_front(values: &[u8]) -> Vec<u8> {
+    let mut recent: Vec<u8> = (0..=255).collect();
+    values.iter().map(|&value| {
+        let byte = recent[value as usize];
+        recent.copy_within(0..value as usize, 1);
+        recent[0] = byte;
+        byte
+    }).collect()
+}
+
+// follows each sorted row to the row starting one byte earlier, rebuilding the block from its last byte to its first
+fn undo_burrows_wheeler(last_bytes: &[u8], primary: usize) -> io::Result<Vec<u8>> {
+    // the row of the whole block ends with the sentinel, which isn't stored
+    let last_byte = |row: usize| last_bytes[row - (row > primary) as usize];
+    let mut counts = [0usize; 256];
+    for &byte in last_bytes {
+        counts[byte as usize] += 1;
+    }
+    // rows start with the sentinel, then each byte in order
+    let mut starts = [0usize; 256];
+    let mut start = 1;
+    for (byte_start, count) in starts.iter_mut().zip(counts) {
+        *byte_start = start;
+        start += count;
+    }
+    let mut prev_rows = vec![0u32; last_bytes.len() + 1];
+    for (row, prev_row) in prev_rows.iter_mut().enumerate() {
+        if row != primary {
+            let byte = last_byte(row) as usize;
+            *prev_row = starts[byte] as u32;
+            starts[byte] += 1;
+        }
+    }
+
+    // the first row is the sentinel alone, which ends with the block's last byte
+    let mut block = vec![0u8; last_bytes.len()];
+    let mut row = 0;
+    for byte in block.iter_mut().rev() {
+        if row == primary {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Sorted rows don't form a single block"));
+        }
+        *byte = last_byte(row);
+        row = prev_rows[row] as usize;
+    }
+    Ok(block)
+}
+
+impl EntryDecoder for BwtDecoder {
+    fn decode(&mut self, reader: &mut BitReader<dyn Read + '_>, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() && self.position < self.byte_size {
+            if self.buffer_position == self.buffer.len() {
+                self.decode_block(reader)?;
+            }
+            let copy_len = (buf.len() - len).min(self.buffer.len() - self.buffer_position);
+            buf[len..len + copy_len].copy_from_slice(&self.buffer[self.buffer_position..self.buffer_position + copy_len]);
+            self.buffer_position += copy_len;
+            self.position += copy_len as u64;
+            len += copy_len;
+        }
+        Ok(len)
+    }
+
+    fn seek_chunk(&mut self, position: u64) -> Option<(u64, u64)> {
+        let block = (position / self.block_size).min(self.block_bit_offsets.len().saturating_sub(1) as u64);
+        let block_bit_offset = *self.block_bit_offsets.get(block as usize)?;
+        self.buffer.clear();
+        self.buffer_position = 0;
+        self.position = block * self.block_size;
+        Some((self.position, block_bit_offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::compress::tests::noise;
+    use super::*;
+
+    #[test]
+    fn banana_sorts_to_its_known_transform() {
+        // the rows of "banana$" end with "annb$aa", the sentinel's row being the whole block
+        let (primary, last_bytes) = burrows_wheeler(b"banana");
+        assert_eq!((primary, last_bytes.as_slice()), (4, &b"annbaa"[..]));
+        let values = move_to_front(&last_bytes);
+        assert_eq!(values, [97, 110, 0, 99, 2, 0]);
+        assert_eq!(run_length_zeros(&values), [98, 111, RUN_A, 100, 3, RUN_A]);
+
+        assert_eq!(undo_move_to_front(&values), last_bytes);
+        assert_eq!(undo_burrows_wheeler(&last_bytes, primary as usize).unwrap(), b"banana");
+    }
+
+    #[test]
+    fn runs_of_zeros_are_bijective_base_two() {
+        let runs: Vec<Vec<u16>> = (1..=5).map(|run| run_length_zeros(&vec![0; run])).collect();
+        assert_eq!(runs, [vec![RUN_A], vec![RUN_B], vec![RUN_A, RUN_A], vec![RUN_B, RUN_A], vec![RUN_A, RUN_B]]);
+    }
+
+    #[test]
+    fn suffixes_sort_like_a_plain_sort() {
+        for block in [vec![], vec![0; 100], b"abracadabra".repeat(20), noise(500)] {
+            let mut expected: Vec<u32> = (0..=block.len() as u32).collect();
+            expected.sort_by_key(|&suffix| &block[suffix as usize..]);
+            assert_eq!(suffix_array(&block), expected);
+        }
+    }
+
+    #[test]
+    fn entries_round_trip_across_blocks() {
+        let input = [b"the quick brown fox jumps over the lazy dog. ".repeat(40), noise(1000), vec![0; 1000]].concat();
+        let codec = BwtCodec::new(777, DEFAULT_MAX_CODE_LEN);
+        let encoder = codec.create_encoder(&mut input.as_slice()).unwrap();
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        encoder.encode(&mut input.as_slice(), &mut writer).unwrap();
+        assert_eq!(writer.write_len(), encoder.header_bit_size() + encoder.data_bit_size().unwrap());
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut block = FileBlock::new(b"entry", Path::new(""));
+        block.original_byte_size = input.len() as u64;
+        block.tree_bit_size = encoder.header_bit_size();
+        block.data_bit_size = encoder.data_bit_size().unwrap();
+        let mut reader = BitReader::new(bytes.as_slice());
+        let mut decoder = codec.create_decoder(&mut reader, &block).ok().unwrap();
+        let mut decoded = vec![0; input.len()];
+        let mut len = 0;
+        while len < decoded.len() {
+            len += decoder.decode(&mut reader, &mut decoded[len..]).unwrap();
+        }
+        assert_eq!(decoded, input);
+
+        // the index can't point past the entry's data, or overflow on the way there
+        bytes[4..12].copy_from_slice(&u64::MAX.to_le_bytes());
+        let result = codec.create_decoder(&mut BitReader::new(bytes.as_slice()), &block);
+        assert!(matches!(result, Err(ZipperError::MalformedTree)));
+        bytes[4..12].copy_from_slice(&(block.data_bit_size / 8 + 1).to_le_bytes());
+        let result = codec.create_decoder(&mut BitReader::new(bytes.as_slice()), &block);
+        assert!(matches!(result, Err(ZipperError::MalformedTree)));
+    }
+}
@@ -0,0 +1,135 @@
+// Joseph Prichard
+// 10/18/2026
+// Canonical huffman codes, rebuilt from nothing but the length of each symbol's code
+
+use std::io;
+use std::io::{ErrorKind, Read};
+use crate::bitwise::SymbolCode;
+use crate::read::BitReader;
+
+// the longest code a symbol code can hold
+pub const MAX_CODE_LEN: u8 = 32;
+
+// assigns codes in order of length then symbol, so the lengths alone determine every code
+// a length of zero means the symbol is unused and gets no code
+pub fn create_canonical_table(code_lens: &[u8]) -> Vec<SymbolCode> {
+    let mut symbol_table = vec![SymbolCode::new(); code_lens.len()];
+    let mut code: u64 = 0;
+    for bit_len in 1..=MAX_CODE_LEN {
+        for (symbol, &code_len) in code_lens.iter().enumerate() {
+            if code_len == bit_len {
+                // codes are written from their first bit, so they are stored reversed
+                symbol_table[symbol] = SymbolCode {
+                    plain_symbol: symbol as u16,
+                    encoded_symbol: (code as u32).reverse_bits() >> (32 - bit_len as u32),
+                    bit_len
+                };
+                code += 1;
+            }
+        }
+        code <<= 1;
+    }
+    symbol_table
+}
+
+// the lengths form a complete prefix code when every bit sequence starts with exactly one code
+pub fn is_complete_code(code_lens: &[u8]) -> bool {
+    let mut kraft_sum: u64 = 0;
+    for &code_len in code_lens {
+        if code_len > MAX_CODE_LEN {
+            return false;
+        }
+        if code_len > 0 {
+            kraft_sum += 1 << (MAX_CODE_LEN - code_len);
+        }
+    }
+    kraft_sum == 1 << MAX_CODE_LEN
+}
+
+// decodes canonical codes by counting how many codes there are of each length
+#[derive(Clone)]
+pub struct DecodeTable {
+    // the number of codes of each length
+    counts: Vec<u64>,
+    // the symbols ordered by code length then symbol, the same order codes are assigned in
+    symbols: Vec<u16>
+}
+
+impl DecodeTable {
+    // the lengths must form a complete code
+    pub fn new(code_lens: &[u8]) -> DecodeTable {
+        let mut counts = vec![0; MAX_CODE_LEN as usize + 1];
+        let mut symbols = vec![];
+        for bit_len in 1..=MAX_CODE_LEN {
+            for (symbol, &code_len) in code_lens.iter().enumerate() {
+                if code_len == bit_len {
+                    counts[bit_len as usize] += 1;
+                    symbols.push(symbol as u16);
+                }
+            }
+        }
+        DecodeTable { counts, symbols }
+    }
+
+    pub fn decode_next_symbol<R: Read + ?Sized>(&self, reader: &mut BitReader<R>) -> io::Result<u16> {
+        // the first code of each length follows the codes of the previous length
+        let mut code = 0;
+        let mut first = 0;
+        let mut index = 0;
+        for &count in &self.counts[1..] {
+            code |= reader.read_bit()? as u64;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(io::Error::new(ErrorKind::InvalidData, "Code is longer than any code in the table"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use crate::write::BitWriter;
+    use super::*;
+
+    #[test]
+    fn codes_are_assigned_by_length_then_symbol() {
+        // codes 10, 0, 110, and 111, stored reversed
+        let symbol_table = create_canonical_table(&[2, 1, 3, 3, 0]);
+        let codes: Vec<(u32, u8)> = symbol_table.iter().map(|code| (code.encoded_symbol, code.bit_len)).collect();
+        assert_eq!(codes, [(0b01, 2), (0b0, 1), (0b011, 3), (0b111, 3), (0, 0)]);
+    }
+
+    #[test]
+    fn only_complete_codes_are_accepted() {
+        assert!(is_complete_code(&[2, 1, 3, 3, 0]));
+        assert!(!is_complete_code(&[2, 1, 3, 0]));
+        assert!(!is_complete_code(&[1, 1, 1]));
+        assert!(!is_complete_code(&[]));
+        assert!(!is_complete_code(&[MAX_CODE_LEN + 1, 1]));
+    }
+
+    #[test]
+    fn decoded_symbols_match_the_written_codes() {
+        let code_lens = [3, 3, 3, 3, 3, 2, 4, 4];
+        assert!(is_complete_code(&code_lens));
+        let symbol_table = create_canonical_table(&code_lens);
+        let symbols = [0u16, 5, 7, 6, 1, 2, 3, 4, 5, 5];
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        for &symbol in &symbols {
+            writer.write_symbol(&symbol_table[symbol as usize]).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let decode_table = DecodeTable::new(&code_lens);
+        let mut reader = BitReader::new(Cursor::new(bytes));
+        for &symbol in &symbols {
+            assert_eq!(decode_table.decode_next_symbol(&mut reader).unwrap(), symbol);
+        }
+    }
+}
@@ -1,9 +1,22 @@
 // Joseph Prichard
 // 1/5/2023
-// Char code globals for file format
+// Globals for file format
 
 use crate::utils::str_to_u64;
 
-pub const REC_SEP: u8 = 0x1E;
-pub const GRP_SEP: u8 = 0x1D;
-pub const SIG: u64 = str_to_u64("zipper");
\ No newline at end of file
+pub const SIG: u64 = str_to_u64("zipper");
+// the start of a dictionary file written by training
+pub const DICT_SIG: u64 = str_to_u64("zipdict");
+
+// major version changes can't be parsed by older readers, minor version changes can
+pub const VERSION_MAJOR: u16 = 3;
+pub const VERSION_MINOR: u16 = 3;
+
+// flags in the low half must be understood by a reader, flags in the high half are optional
+pub const REQUIRED_FLAGS_MASK: u32 = 0x0000FFFF;
+// the data segment starts with one code table shared by every entry compressed with the solid method
+pub const FLAG_SOLID: u32 = 0x00000001;
+// the solid stream has several code tables, preceded by their count, and each solid entry names the one it uses
+pub const FLAG_CODE_BOOKS: u32 = 0x00000002;
+// every flag this version knows how to read
+pub const SUPPORTED_FLAGS: u32 = FLAG_SOLID | FLAG_CODE_BOOKS;
@@ -0,0 +1,103 @@
+// Joseph Prichard
+// 10/18/2026
+// CRC-32 checksums for verifying the integrity of archived files
+
+use std::io;
+use std::io::Read;
+
+// reversed polynomial used by zip, gzip, and png
+const POLYNOMIAL: u32 = 0xEDB88320;
+const CRC_TABLE: [u32; 256] = create_crc_table();
+
+const fn create_crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+#[derive(Clone, Copy)]
+pub struct Crc32 {
+    value: u32
+}
+
+impl Crc32 {
+    pub fn new() -> Crc32 {
+        Crc32 { value: 0xFFFFFFFF }
+    }
+
+    pub fn update(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            let i = ((self.value ^ byte as u32) & 0xFF) as usize;
+            self.value = (self.value >> 8) ^ CRC_TABLE[i];
+        }
+    }
+
+    pub fn checksum(&self) -> u32 {
+        !self.value
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Crc32 {
+        Crc32::new()
+    }
+}
+
+// passes reads through to the inner reader while computing a checksum of the bytes read
+pub struct ChecksumReader<R: Read> {
+    inner: R,
+    crc: Crc32,
+    // the number of bytes read so far
+    byte_count: u64
+}
+
+impl<R: Read> ChecksumReader<R> {
+    pub fn new(inner: R) -> ChecksumReader<R> {
+        ChecksumReader { inner, crc: Crc32::new(), byte_count: 0 }
+    }
+
+    pub fn checksum(&self) -> u32 {
+        self.crc.checksum()
+    }
+
+    pub fn byte_count(&self) -> u64 {
+        self.byte_count
+    }
+}
+
+impl<R: Read> Read for ChecksumReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.crc.update(&buf[..len]);
+        self.byte_count += len as u64;
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums_match_the_standard_check_value() {
+        let mut crc = Crc32::new();
+        crc.update(b"123456789");
+        assert_eq!(crc.checksum(), 0xCBF43926);
+        assert_eq!(Crc32::new().checksum(), 0);
+
+        // checksumming through a reader matches checksumming the whole buffer at once
+        let mut reader = ChecksumReader::new(&b"123456789"[..]);
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+        assert_eq!(reader.checksum(), 0xCBF43926);
+    }
+}
@@ -0,0 +1,244 @@
+// Joseph Prichard
+// 10/18/2026
+// Chunked huffman codec, splitting files into chunks that each get their own code table and can be decoded on their own
+
+use std::io;
+use std::io::{Read, Write};
+use crate::bitwise::SymbolCode;
+use crate::block::FileBlock;
+use crate::canonical::{DecodeTable, MAX_CODE_LEN};
+use crate::codec::{Codec, EntryDecoder, EntryEncoder, METHOD_CHUNKED};
+use crate::error::{Result, ZipperError};
+use crate::huffman;
+use crate::huffman::{tree_error, DEFAULT_MAX_CODE_LEN, TABLE_SIZE};
+use crate::read::BitReader;
+use crate::write::BitWriter;
+
+// the byte size of each chunk unless a different size is set
+pub const DEFAULT_CHUNK_SIZE: u32 = 1 << 20;
+
+pub struct ChunkedCodec {
+    // the number of bytes of the original file in each chunk, the last chunk holds whatever is left
+    chunk_size: u32,
+    // the longest code any symbol is given
+    max_code_len: u8
+}
+
+impl ChunkedCodec {
+    pub fn new(chunk_size: u32, max_code_len: u8) -> ChunkedCodec {
+        ChunkedCodec {
+            chunk_size: chunk_size.max(1),
+            max_code_len: max_code_len.min(MAX_CODE_LEN)
+        }
+    }
+}
+
+impl Default for ChunkedCodec {
+    fn default() -> ChunkedCodec {
+        ChunkedCodec::new(DEFAULT_CHUNK_SIZE, DEFAULT_MAX_CODE_LEN)
+    }
+}
+
+impl Codec for ChunkedCodec {
+    fn method(&self) -> u8 {
+        METHOD_CHUNKED
+    }
+
+    fn name(&self) -> &'static str {
+        "chunked"
+    }
+
+    fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>> {
+        let mut chunks = vec![];
+        loop {
+            let freq_table = huffman::create_freq_table((&mut *input).take(self.chunk_size as u64))?;
+            let byte_size: u64 = freq_table.iter().sum();
+            if byte_size == 0 {
+                break;
+            }
+            let tree = huffman::create_code_tree(&freq_table, self.max_code_len);
+            let symbol_table = huffman::create_code_table(&tree, TABLE_SIZE);
+            // each chunk starts on a byte so it can be jumped to
+            let mut bit_size = huffman::code_lengths_bit_size(&symbol_table);
+            for (freq, symbol_code) in freq_table.iter().zip(&symbol_table) {
+                bit_size += freq * (symbol_code.bit_len as u64);
+            }
+            chunks.push(Chunk { symbol_table, byte_size, compressed_byte_size: bit_size.div_ceil(8) });
+        }
+        Ok(Box::new(ChunkedEncoder { chunk_size: self.chunk_size, chunks }))
+    }
+
+    fn create_decoder(&self, reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>> {
+        let chunk_size = reader.read_u32().map_err(tree_error)?;
+        if chunk_size == 0 {
+            return Err(ZipperError::MalformedTree);
+        }
+        // the index holds where each chunk starts, counted in bytes from the end of the index
+        let chunk_count = block.original_byte_size.div_ceil(chunk_size as u64);
+        if chunk_count != block.tree_bit_size.saturating_sub(32) / 64 {
+            return Err(ZipperError::MalformedTree);
+        }
+        // the offsets are kept in bits, and every chunk has to end within the entry's data
+        let mut chunk_bit_offsets = vec![];
+        let mut chunk_offset: u64 = 0;
+        for _ in 0..chunk_count {
+            let chunk_bit_offset = chunk_offset.checked_mul(8).ok_or(ZipperError::MalformedTree)?;
+            chunk_bit_offsets.push(chunk_bit_offset);
+            chunk_offset = chunk_offset
+                .checked_add(reader.read_u64().map_err(tree_error)?)
+                .ok_or(ZipperError::MalformedTree)?;
+        }
+        if chunk_offset.checked_mul(8).is_none_or(|bit_size| bit_size > block.data_bit_size) {
+            return Err(ZipperError::MalformedTree);
+        }
+        Ok(Box::new(ChunkedDecoder {
+            chunk_size: chunk_size as u64,
+            chunk_bit_offsets,
+            table: None,
+            position: 0,
+            byte_size: block.original_byte_size
+        }))
+    }
+}
+
+struct Chunk {
+    symbol_table: Vec<SymbolCode>,
+    // the byte size of the original bytes in the chunk
+    byte_size: u64,
+    // the byte size of the chunk's code table and compressed data
+    compressed_byte_size: u64
+}
+
+struct ChunkedEncoder {
+    chunk_size: u32,
+    chunks: Vec<Chunk>
+}
+
+impl EntryEncoder for ChunkedEncoder {
+    // the chunk size then the compressed size of every chunk
+    fn header_bit_size(&self) -> u64 {
+        32 + 64 * self.chunks.len() as u64
+    }
+
+    fn data_bit_size(&self) -> Option<u64> {
+        Some(self.chunks.iter().map(|chunk| chunk.compressed_byte_size * 8).sum())
+    }
+
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()> {
+        writer.write_u32(self.chunk_size)?;
+        for chunk in &self.chunks {
+            writer.write_u64(chunk.compressed_byte_size)?;
+        }
+        for chunk in &self.chunks {
+            huffman::write_code_lengths(writer, &chunk.symbol_table)?;
+            // stop at the size the codes were built for in case the file has grown since
+            huffman::compress_file((&mut *input).take(chunk.byte_size), writer, &chunk.symbol_table)?;
+            writer.align_to_byte();
+        }
+        Ok(())
+    }
+}
+
+struct ChunkedDecoder {
+    chunk_size: u64,
+    // the bit offset of each chunk from the end of the index
+    chunk_bit_offsets: Vec<u64>,
+    // the code table of the chunk being decoded, read at the start of each chunk
+    table: Option<DecodeTable>,
+    // the number of bytes of the entry decoded so far
+    position: u64,
+    byte_size: u64
+}
+
+impl EntryDecoder for ChunkedDecoder {
+    fn decode(&mut self, reader: &mut BitReader<dyn Read + '_>, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() && self.position < self.byte_size {
+            let table = match &self.table {
+                Some(table) => table,
+                None => {
+                    reader.align_to_byte();
+                    self.table.insert(huffman::read_code_lengths(reader, TABLE_SIZE)?)
+                }
+            };
+            buf[len] = table.decode_next_symbol(reader)? as u8;
+            self.position += 1;
+            len += 1;
+            if self.position.is_multiple_of(self.chunk_size) {
+                self.table = None;
+            }
+        }
+        Ok(len)
+    }
+
+    fn seek_chunk(&mut self, position: u64) -> Option<(u64, u64)> {
+        let chunk = (position / self.chunk_size).min(self.chunk_bit_offsets.len().saturating_sub(1) as u64);
+        let chunk_bit_offset = *self.chunk_bit_offsets.get(chunk as usize)?;
+        self.table = None;
+        self.position = chunk * self.chunk_size;
+        Some((self.position, chunk_bit_offset))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use super::*;
+
+    // an index for an eight byte entry in chunks of four, with the given compressed chunk sizes
+    fn read_index(chunk_sizes: [u64; 2], data_bit_size: u64) -> Result<Box<dyn EntryDecoder>> {
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_u32(4).unwrap();
+        for chunk_size in chunk_sizes {
+            writer.write_u64(chunk_size).unwrap();
+        }
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut block = FileBlock::new(b"entry", Path::new(""));
+        block.original_byte_size = 8;
+        block.tree_bit_size = 32 + 64 * 2;
+        block.data_bit_size = data_bit_size;
+        ChunkedCodec::default().create_decoder(&mut BitReader::new(bytes.as_slice()), &block)
+    }
+
+    #[test]
+    fn indexes_that_overflow_or_overrun_the_entry_are_refused() {
+        let mut decoder = read_index([3, 5], 64).ok().unwrap();
+        assert_eq!(decoder.seek_chunk(6), Some((4, 24)));
+        assert!(matches!(read_index([u64::MAX, 1], 64), Err(ZipperError::MalformedTree)));
+        assert!(matches!(read_index([u64::MAX / 8, 1], u64::MAX), Err(ZipperError::MalformedTree)));
+        assert!(matches!(read_index([3, 6], 64), Err(ZipperError::MalformedTree)));
+    }
+
+    #[test]
+    fn each_chunk_gets_its_own_code_table() {
+        // the first chunk uses only a few bytes and the second many, which one table would code worse
+        let input = [b"aaaaaaaaab".repeat(100), (0..1000).map(|i| i as u8).collect()].concat();
+        let codec = ChunkedCodec::new(1000, DEFAULT_MAX_CODE_LEN);
+        let encoder = codec.create_encoder(&mut input.as_slice()).unwrap();
+        assert_eq!(encoder.header_bit_size(), 32 + 64 * 2);
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        encoder.encode(&mut input.as_slice(), &mut writer).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert_eq!(bytes.len() as u64 * 8, encoder.header_bit_size() + encoder.data_bit_size().unwrap());
+        let whole = huffman::HuffmanCodec::default().create_encoder(&mut input.as_slice()).unwrap();
+        assert!(encoder.header_bit_size() + encoder.data_bit_size().unwrap() < whole.header_bit_size() + whole.data_bit_size().unwrap());
+
+        let mut block = FileBlock::new(b"entry", Path::new(""));
+        block.original_byte_size = input.len() as u64;
+        block.tree_bit_size = encoder.header_bit_size();
+        block.data_bit_size = encoder.data_bit_size().unwrap();
+        let mut reader = BitReader::new(bytes.as_slice());
+        let mut decoder = codec.create_decoder(&mut reader, &block).ok().unwrap();
+        let mut decoded = vec![0; input.len()];
+        let mut len = 0;
+        while len < decoded.len() {
+            len += decoder.decode(&mut reader, &mut decoded[len..]).unwrap();
+        }
+        assert_eq!(decoded, input);
+    }
+}
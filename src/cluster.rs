@@ -0,0 +1,156 @@
+// Joseph Prichard
+// 10/18/2026
+// K-means clustering of byte histograms, grouping files whose bytes are distributed alike so they can share a code table
+
+// the most rounds of reassigning files before the clusters are taken as they are
+const MAX_ROUNDS: usize = 32;
+// added to every byte count of a cluster so bytes it hasn't seen still have a probability
+const SMOOTHING: f64 = 0.5;
+
+// the probabilities of a cluster's combined histogram, kept as the bits a byte costs under them
+struct Centroid {
+    bit_costs: Vec<f64>
+}
+
+impl Centroid {
+    fn new(freq_table: &[u64]) -> Centroid {
+        let total = freq_table.iter().sum::<u64>() as f64 + SMOOTHING * freq_table.len() as f64;
+        let bit_costs = freq_table
+            .iter()
+            .map(|&freq| -((freq as f64 + SMOOTHING) / total).log2())
+            .collect();
+        Centroid { bit_costs }
+    }
+
+    // the kl divergence of the file's bytes from the cluster's, weighted by the file size
+    // this is the number of bits the file loses by being coded for the cluster rather than for itself
+    fn divergence(&self, freq_table: &[u64]) -> f64 {
+        let total = freq_table.iter().sum::<u64>() as f64;
+        let mut divergence = 0.0;
+        for (&freq, bit_cost) in freq_table.iter().zip(&self.bit_costs) {
+            if freq > 0 {
+                let freq = freq as f64;
+                divergence += freq * ((freq / total).log2() + bit_cost);
+            }
+        }
+        divergence
+    }
+}
+
+// the closest centroid to the file and the file's divergence from it
+fn nearest(freq_table: &[u64], centroids: &[Centroid]) -> (usize, f64) {
+    centroids
+        .iter()
+        .map(|centroid| centroid.divergence(freq_table))
+        .enumerate()
+        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+        .expect("There is at least one centroid")
+}
+
+// groups the histograms into at most the number of clusters, returning the cluster of each histogram
+// clusters are numbered from zero in order of their first histogram, with no empty clusters in between
+pub(crate) fn cluster_freq_tables(freq_tables: &[Vec<u64>], cluster_count: usize) -> Vec<usize> {
+    if freq_tables.is_empty() {
+        return vec![];
+    }
+    let cluster_count = cluster_count.clamp(1, freq_tables.len());
+
+    // the first cluster starts from the largest file, and each next one from the file furthest from every cluster so far
+    let largest = (0..freq_tables.len())
+        .max_by_key(|&i| freq_tables[i].iter().sum::<u64>())
+        .expect("There is at least one histogram");
+    let mut centroids = vec![Centroid::new(&freq_tables[largest])];
+    while centroids.len() < cluster_count {
+        let (furthest, divergence) = freq_tables
+            .iter()
+            .map(|freq_table| nearest(freq_table, &centroids).1)
+            .enumerate()
+            .max_by(|(_, a), (_, b)| a.total_cmp(b))
+            .expect("There is at least one histogram");
+        // every file already fits a cluster as well as it fits itself
+        if divergence <= 0.0 {
+            break;
+        }
+        centroids.push(Centroid::new(&freq_tables[furthest]));
+    }
+
+    // move each file to its closest cluster, then rebuild each cluster from its files, until no file moves
+    // the combined histogram of a cluster's files is the centroid the files diverge from the least
+    let mut clusters = vec![usize::MAX; freq_tables.len()];
+    for _ in 0..MAX_ROUNDS {
+        let mut moved = false;
+        for (cluster, freq_table) in clusters.iter_mut().zip(freq_tables) {
+            let (nearest_cluster, _) = nearest(freq_table, &centroids);
+            moved |= *cluster != nearest_cluster;
+            *cluster = nearest_cluster;
+        }
+        if !moved {
+            break;
+        }
+        let mut cluster_freq_tables = vec![vec![0u64; freq_tables[0].len()]; centroids.len()];
+        for (&cluster, freq_table) in clusters.iter().zip(freq_tables) {
+            for (cluster_freq, freq) in cluster_freq_tables[cluster].iter_mut().zip(freq_table) {
+                *cluster_freq += freq;
+            }
+        }
+        centroids = cluster_freq_tables.iter().map(|freq_table| Centroid::new(freq_table)).collect();
+    }
+
+    // clusters that lost all their files are dropped from the numbering
+    let mut numbering = vec![None; centroids.len()];
+    let mut next_number = 0;
+    for cluster in &mut clusters {
+        *cluster = *numbering[*cluster].get_or_insert_with(|| {
+            next_number += 1;
+            next_number - 1
+        });
+    }
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a histogram of the bytes spread evenly over the range
+    fn histogram(bytes: std::ops::Range<usize>, count: u64) -> Vec<u64> {
+        let mut freq_table = vec![0u64; 256];
+        for byte in bytes {
+            freq_table[byte] = count;
+        }
+        freq_table
+    }
+
+    #[test]
+    fn files_with_different_bytes_are_split() {
+        let freq_tables = vec![
+            histogram(97..123, 40),
+            histogram(0..256, 10),
+            histogram(97..123, 7),
+            histogram(128..256, 30),
+            histogram(97..110, 90),
+            histogram(0..256, 3)
+        ];
+        let clusters = cluster_freq_tables(&freq_tables, 2);
+        assert_eq!(clusters[0], 0);
+        assert_eq!(clusters[2], clusters[0]);
+        assert_eq!(clusters[4], clusters[0]);
+        assert_eq!(clusters[1], 1);
+        assert_eq!(clusters[3], clusters[1]);
+        assert_eq!(clusters[5], clusters[1]);
+    }
+
+    #[test]
+    fn clusters_are_numbered_densely() {
+        let freq_tables = vec![histogram(0..10, 5), histogram(100..110, 5), histogram(200..210, 5), histogram(0..10, 9)];
+        assert_eq!(cluster_freq_tables(&freq_tables, 8), [0, 1, 2, 0]);
+        assert_eq!(cluster_freq_tables(&freq_tables, 1), [0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn alike_files_share_one_cluster() {
+        let freq_tables = vec![histogram(0..50, 4); 5];
+        assert_eq!(cluster_freq_tables(&freq_tables, 3), [0; 5]);
+        assert!(cluster_freq_tables(&[], 3).is_empty());
+    }
+}
@@ -0,0 +1,247 @@
+// Joseph Prichard
+// 10/18/2026
+// Compression methods that can encode the contents of entries in an archive
+
+use std::collections::HashMap;
+use std::io;
+use std::io::{Read, Write};
+use crate::adaptive::AdaptiveCodec;
+use crate::block::FileBlock;
+use crate::bwt::BwtCodec;
+use crate::chunked::ChunkedCodec;
+use crate::context::ContextCodec;
+use crate::dictionary::DictionaryCodec;
+use crate::rans::RansCodec;
+use crate::error::Result;
+use crate::huffman::HuffmanCodec;
+use crate::lz77::Lz77Codec;
+use crate::read::BitReader;
+use crate::write::BitWriter;
+
+// the method ids stored in the header of each block
+pub const METHOD_STORED: u8 = 0;
+pub const METHOD_HUFFMAN: u8 = 1;
+pub const METHOD_LZ77: u8 = 2;
+pub const METHOD_ADAPTIVE: u8 = 3;
+pub const METHOD_CHUNKED: u8 = 4;
+pub const METHOD_CONTEXT: u8 = 5;
+pub const METHOD_RANS: u8 = 6;
+pub const METHOD_BWT: u8 = 7;
+// entries coded with the code table shared by a solid archive, which isn't a codec of its own
+pub const METHOD_SOLID: u8 = 8;
+pub const METHOD_DICTIONARY: u8 = 9;
+
+// the names of the built in methods, indexed by their method id
+const METHOD_NAMES: [&str; 10] = ["stored", "huffman", "lz77", "adaptive", "chunked", "context", "rans", "bwt", "solid", "dictionary"];
+
+// a compression algorithm, identified in the archive by its method id
+pub trait Codec {
+    fn method(&self) -> u8;
+
+    fn name(&self) -> &'static str;
+
+    // reads all of the input once to build an encoder, such as a code table, for a second pass
+    fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>>;
+
+    // reads whatever the encoder wrote ahead of the data, returning a decoder for the data
+    fn create_decoder(&self, reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>>;
+
+    // whether the decoders of this codec know the bit size their entries would take huffman coded
+    fn stores_huffman_bit_size(&self) -> bool {
+        false
+    }
+}
+
+// encodes one file, knowing the exact size it will write before writing it
+pub trait EntryEncoder {
+    // the bits written ahead of the encoded data, such as a code table
+    fn header_bit_size(&self) -> u64;
+
+    // none when the size is only known once the data is encoded, the file is then encoded once into nothing to measure it
+    fn data_bit_size(&self) -> Option<u64>;
+
+    // writes the header then the encoded data, reading the input a second time
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()>;
+}
+
+// decodes the data of one entry, a few bytes at a time
+pub trait EntryDecoder {
+    // decodes bytes into the buffer, returning zero once every byte of the entry is decoded
+    fn decode(&mut self, reader: &mut BitReader<dyn Read + '_>, buf: &mut [u8]) -> io::Result<usize>;
+
+    // restarts decoding at the start of the chunk holding the byte of the entry, for codecs that split entries into
+    // chunks that decode on their own, returning the byte the chunk starts at and its bit offset from the end of the header
+    // codecs that can only decode from the start of an entry return none
+    fn seek_chunk(&mut self, _position: u64) -> Option<(u64, u64)> {
+        None
+    }
+
+    // the bit size the entry would take huffman coded, for codecs that store it to compare against
+    fn huffman_bit_size(&self) -> Option<u64> {
+        None
+    }
+}
+
+// the codecs that can be used to read or write an archive, keyed by their method id
+pub struct CodecRegistry {
+    codecs: HashMap<u8, Box<dyn Codec>>
+}
+
+impl CodecRegistry {
+    // a registry without any codecs, not even the stored method
+    pub fn new() -> CodecRegistry {
+        CodecRegistry { codecs: HashMap::new() }
+    }
+
+    // adds the codec, replacing any codec already registered with the same method id
+    pub fn register(&mut self, codec: Box<dyn Codec>) {
+        self.codecs.insert(codec.method(), codec);
+    }
+
+    pub fn get(&self, method: u8) -> Option<&dyn Codec> {
+        self.codecs.get(&method).map(|codec| codec.as_ref())
+    }
+
+    pub fn by_name(&self, name: &str) -> Option<&dyn Codec> {
+        self.codecs.values().find(|codec| codec.name() == name).map(|codec| codec.as_ref())
+    }
+}
+
+// the codecs built into zipper
+impl Default for CodecRegistry {
+    fn default() -> CodecRegistry {
+        let mut codecs = CodecRegistry::new();
+        codecs.register(Box::new(StoredCodec));
+        codecs.register(Box::new(HuffmanCodec::default()));
+        codecs.register(Box::new(Lz77Codec::default()));
+        codecs.register(Box::new(AdaptiveCodec));
+        codecs.register(Box::new(ChunkedCodec::default()));
+        codecs.register(Box::new(ContextCodec::default()));
+        codecs.register(Box::new(RansCodec));
+        codecs.register(Box::new(BwtCodec::default()));
+        // without a dictionary this only reports which dictionary an entry needs
+        codecs.register(Box::new(DictionaryCodec::default()));
+        codecs
+    }
+}
+
+// the name of a built in method, or its id for methods that aren't built in
+pub fn method_name(method: u8) -> String {
+    match METHOD_NAMES.get(method as usize) {
+        Some(name) => String::from(*name),
+        None => format!("#{}", method)
+    }
+}
+
+// copies the contents without any compression, used when compressing would make a file larger
+pub struct StoredCodec;
+
+impl Codec for StoredCodec {
+    fn method(&self) -> u8 {
+        METHOD_STORED
+    }
+
+    fn name(&self) -> &'static str {
+        "stored"
+    }
+
+    fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>> {
+        let byte_size = io::copy(input, &mut io::sink())?;
+        Ok(Box::new(StoredEncoder::new(byte_size)))
+    }
+
+    fn create_decoder(&self, _reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>> {
+        Ok(Box::new(StoredDecoder { remaining: block.original_byte_size }))
+    }
+}
+
+pub struct StoredEncoder {
+    byte_size: u64
+}
+
+impl StoredEncoder {
+    pub fn new(byte_size: u64) -> StoredEncoder {
+        StoredEncoder { byte_size }
+    }
+}
+
+impl EntryEncoder for StoredEncoder {
+    fn header_bit_size(&self) -> u64 {
+        0
+    }
+
+    fn data_bit_size(&self) -> Option<u64> {
+        Some(self.byte_size * 8)
+    }
+
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()> {
+        // stop at the size the file was archived with in case it has grown since
+        let mut reader = BitReader::new(input.take(self.byte_size));
+        for _ in 0..self.byte_size {
+            writer.write_byte(reader.read_byte()?)?;
+        }
+        Ok(())
+    }
+}
+
+struct StoredDecoder {
+    // the number of bytes left to copy
+    remaining: u64
+}
+
+impl EntryDecoder for StoredDecoder {
+    fn decode(&mut self, reader: &mut BitReader<dyn Read + '_>, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() && self.remaining > 0 {
+            buf[len] = reader.read_byte()?;
+            self.remaining -= 1;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn built_in_codecs_are_found_by_id_and_name() {
+        let codecs = CodecRegistry::default();
+        for method in 0..=u8::MAX {
+            match codecs.get(method) {
+                Some(codec) => {
+                    assert_eq!(codec.method(), method);
+                    assert_eq!(method_name(method), codec.name());
+                    assert_eq!(codecs.by_name(codec.name()).unwrap().method(), method);
+                }
+                // solid entries are decoded from the archive's shared stream, not by a codec
+                None if method == METHOD_SOLID => assert_eq!(method_name(method), "solid"),
+                None => assert_eq!(method_name(method), format!("#{}", method))
+            }
+        }
+        assert!(CodecRegistry::new().get(METHOD_STORED).is_none());
+    }
+
+    #[test]
+    fn stored_entries_are_copied_up_to_their_archived_size() {
+        // the file grew after its size was taken, so only the bytes it was archived with are copied
+        let encoder = StoredCodec.create_encoder(&mut &b"hello"[..]).unwrap();
+        assert_eq!((encoder.header_bit_size(), encoder.data_bit_size()), (0, Some(40)));
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        encoder.encode(&mut &b"hello world"[..], &mut writer).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert_eq!(bytes, b"hello");
+
+        let mut block = FileBlock::new(b"hello", std::path::Path::new(""));
+        block.original_byte_size = 5;
+        let mut reader = BitReader::new(&bytes[..]);
+        let mut decoder = StoredCodec.create_decoder(&mut reader, &block).unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(decoder.decode(&mut reader, &mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"hello");
+        assert_eq!(decoder.decode(&mut reader, &mut buf).unwrap(), 0);
+    }
+}
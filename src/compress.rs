@@ -2,200 +2,646 @@
 // 1/5/2023
 // Byte-by-byte file compressor
 
-use std::collections::{BinaryHeap};
-use std::{fs};
+use std::collections::hash_map::Entry;
+use std::collections::HashMap;
+use std::{fs, io};
+use std::fs::File;
+use std::io::{ErrorKind, Write};
 use std::path::{Path};
-use std::time::Instant;
+use std::rc::Rc;
+use crate::block::{ArchiveHeader, EntryType, FileBlock};
+use crate::bwt::{BwtCodec, DEFAULT_SORT_BLOCK_SIZE};
+use crate::checksum::ChecksumReader;
+use crate::cluster::cluster_freq_tables;
+use crate::chunked::{ChunkedCodec, DEFAULT_CHUNK_SIZE};
 use crate::bitwise::SymbolCode;
-use crate::debug::{debug_binary_file, debug_tree_file, debug_tree};
-use crate::block::{CodeBook, FileBlock};
-use crate::charset::{GRP_SEP, REC_SEP, SIG};
-use crate::tree::{Node, Tree};
-use crate::read::FileReader;
-use crate::{block, utils};
-use crate::write::FileWriter;
+use crate::charset::{FLAG_CODE_BOOKS, FLAG_SOLID};
+use crate::codec::{Codec, CodecRegistry, EntryEncoder, StoredEncoder, METHOD_DICTIONARY, METHOD_HUFFMAN, METHOD_SOLID, METHOD_STORED};
+use crate::context::ContextCodec;
+use crate::dictionary::{Dictionary, DictionaryCodec};
+use crate::error::{Result, ZipperError};
+use crate::huffman::{code_lengths_bit_size, compress_file, create_code_table, create_code_tree, create_freq_table, write_code_lengths, HuffmanCodec, SolidCodeBook};
+use crate::huffman::{DEFAULT_MAX_CODE_LEN, TABLE_SIZE};
+use crate::lz77::Lz77Codec;
+use crate::sha256::hash_reader;
+use crate::canonical::MAX_CODE_LEN;
+use crate::utils;
+use crate::utils::get_size_of;
+use crate::write::BitWriter;
 
-const TABLE_SIZE: usize = 256;
+// the byte size of blocks emitted by a streaming encoder
+const DEFAULT_BLOCK_SIZE: usize = 1 << 20;
 
-pub fn archive_dir(input_entry: &[String]) {
-    let now = Instant::now();
+// writes a set of files and directories into a new archive
+pub struct ArchiveWriter<W: Write> {
+    // the stream the archive is written to
+    writer: BitWriter<W>,
+    // a block for each file that will be stored in the archive
+    blocks: Vec<FileBlock>,
+    // relative names of files already added, keyed by their device and inode, to detect hard links
+    links: HashMap<(u64, u64), Vec<u8>>,
+    // the codecs files can be compressed with
+    codecs: CodecRegistry,
+    // the method id of the codec files are compressed with
+    method: u8,
+    // the method ids of files added with their own method, keyed by their block index
+    entry_methods: HashMap<usize, u8>,
+    // whether files without their own method share code tables in a single stream
+    solid: bool,
+    // the most code tables the files in the solid stream are clustered into
+    code_book_count: u16,
+    // the longest code any symbol is given
+    max_code_len: u8
+}
 
-    let mut blocks = get_file_blocks(input_entry);
-    create_code_books(&mut blocks);
+impl ArchiveWriter<File> {
+    pub fn create<P: AsRef<Path>>(archive_filepath: P) -> Result<ArchiveWriter<File>> {
+        Ok(ArchiveWriter::new(File::create(archive_filepath)?))
+    }
+}
 
-    let archive_filename = &format!("{}{}", input_entry[0], ".zipr");
-    let writer = &mut FileWriter::new(archive_filename);
-    writer.write_u64(SIG);
+impl<W: Write> ArchiveWriter<W> {
+    pub fn new(inner: W) -> ArchiveWriter<W> {
+        ArchiveWriter {
+            writer: BitWriter::new(inner),
+            blocks: vec![],
+            links: HashMap::new(),
+            codecs: CodecRegistry::default(),
+            method: METHOD_HUFFMAN,
+            entry_methods: HashMap::new(),
+            solid: false,
+            code_book_count: 1,
+            max_code_len: DEFAULT_MAX_CODE_LEN
+        }
+    }
 
-    write_block_headers(writer, &mut blocks);
-    compress_files(writer, &blocks);
+    // limits the length of every huffman code, up to the longest code a symbol code can hold
+    pub fn set_max_code_len(&mut self, max_code_len: u8) {
+        self.max_code_len = max_code_len.min(MAX_CODE_LEN);
+        self.codecs.register(Box::new(HuffmanCodec::new(self.max_code_len)));
+        self.codecs.register(Box::new(Lz77Codec::new(self.max_code_len)));
+        self.codecs.register(Box::new(ChunkedCodec::new(DEFAULT_CHUNK_SIZE, self.max_code_len)));
+        self.codecs.register(Box::new(ContextCodec::new(self.max_code_len)));
+        self.codecs.register(Box::new(BwtCodec::new(DEFAULT_SORT_BLOCK_SIZE, self.max_code_len)));
+    }
 
-    let elapsed = now.elapsed();
-    println!("Finished zipping in {:.2?}", elapsed);
-    block::list_file_blocks(&blocks);
-}
+    // adds a codec that can be picked with set_method, replacing any codec with the same method id
+    pub fn register_codec(&mut self, codec: Box<dyn Codec>) {
+        self.codecs.register(codec);
+    }
 
-fn get_file_blocks(entries: &[String]) -> Vec<FileBlock> {
-    let mut blocks = vec![];
-    for entry in entries {
-        let path = Path::new(entry);
-        walk_path(path.parent().expect("Failed to get parent path"), path, &mut blocks);
+    // picks the codec files are compressed with, files that wouldn't get smaller are still stored
+    pub fn set_method(&mut self, method: u8) -> Result<()> {
+        self.check_method(method)?;
+        self.method = method;
+        Ok(())
+    }
+
+    // codes every file without its own method with one shared code table, as one stream without padding between files
+    // this saves a code table per file for archives of many small files with similar contents
+    pub fn set_solid(&mut self, solid: bool) {
+        self.solid = solid;
+    }
+
+    // compresses files with a trained dictionary, so each entry only stores the dictionary's id rather than a code table
+    // the same dictionary has to be given to read the archive back
+    pub fn set_dictionary(&mut self, dictionary: Dictionary) {
+        self.codecs.register(Box::new(DictionaryCodec::new(dictionary)));
+        self.method = METHOD_DICTIONARY;
+    }
+
+    // clusters the files of a solid archive by how their bytes are distributed, giving each cluster its own code table
+    // this keeps text and binaries from sharing one table in archives that mix them, setting more than one makes it solid
+    pub fn set_code_book_count(&mut self, code_book_count: u16) {
+        self.code_book_count = code_book_count.max(1);
+        self.solid |= code_book_count > 1;
     }
-    blocks
-}
 
-fn walk_path(base_path: &Path, path: &Path, blocks: &mut Vec<FileBlock>) {
-    if path.is_dir() {
-        for entry in fs::read_dir(path).expect("Can't read directory") {
-            let entry = entry.expect("Entry is invalid");
-            let path = entry.path();
-            walk_path(&base_path, &path, blocks);
+    fn check_method(&self, method: u8) -> Result<()> {
+        match self.codecs.get(method) {
+            Some(_) => Ok(()),
+            None => Err(ZipperError::UnsupportedMethod(method))
         }
-    } else {
-        let filename_abs = &String::from(path.to_str().unwrap());
-        let filename_rel = &String::from(path
-            .strip_prefix(base_path)
-            .expect("Couldn't strip prefix from path")
-            .to_str()
-            .unwrap());
-        let mut block = FileBlock::new(filename_rel, filename_abs);
-        block.original_byte_size = utils::dir_entry_size(&path);
-        blocks.push(block);
     }
-}
 
-fn create_code_books(blocks: &mut [FileBlock]) {
-    for block in blocks {
-        create_code_book(block);
+    pub fn blocks(&self) -> &[FileBlock] {
+        &self.blocks
+    }
+
+    // adds a file or recursively adds a directory, stored relative to the entry's parent
+    pub fn add_entry<P: AsRef<Path>>(&mut self, entry: P) -> Result<()> {
+        let path = entry.as_ref();
+        let base_path = path.parent().unwrap_or(Path::new(""));
+        walk_path(base_path, path, &mut self.blocks, &mut self.links)
+    }
+
+    // adds an entry like add_entry, compressing its files with the method rather than the archive's method
+    pub fn add_entry_with_method<P: AsRef<Path>>(&mut self, entry: P, method: u8) -> Result<()> {
+        self.check_method(method)?;
+        let start = self.blocks.len();
+        self.add_entry(entry)?;
+        for index in start..self.blocks.len() {
+            self.entry_methods.insert(index, method);
+        }
+        Ok(())
+    }
+
+    // compresses every added file and writes the archive, returning the written blocks
+    pub fn finish(mut self) -> Result<Vec<FileBlock>> {
+        let duplicates = find_duplicates(&mut self.blocks)?;
+        let solid_tables = if self.solid {
+            create_solid_encoders(&mut self.blocks, &self.entry_methods, self.max_code_len, self.code_book_count)?
+        } else {
+            vec![]
+        };
+        for (index, block) in self.blocks.iter_mut().enumerate() {
+            // only files have contents to compress, files in the solid stream already have their encoder, and
+            // duplicates share the data of the file they duplicate
+            if block.entry_type == EntryType::File && block.encoder.is_none() && !block.is_duplicate() {
+                let method = self.entry_methods.get(&index).copied().unwrap_or(self.method);
+                let codec = self.codecs.get(method).expect("Methods are checked when they are set");
+                create_encoder(block, codec)?;
+            }
+        }
+
+        let writer = &mut self.writer;
+        let mut header = ArchiveHeader::new();
+        if !solid_tables.is_empty() {
+            header.flags |= FLAG_SOLID;
+        }
+        if solid_tables.len() > 1 {
+            header.flags |= FLAG_CODE_BOOKS;
+        }
+        writer.write_archive_header(&header)?;
+
+        write_block_headers(writer, &mut self.blocks, &duplicates, &solid_tables)?;
+        compress_files(writer, &self.blocks, &solid_tables)?;
+        writer.flush()?;
+
+        Ok(self.blocks)
     }
 }
 
-fn create_code_book(block: &mut FileBlock) {
-    let freq_table = create_freq_table(&block.filename_abs);
-    let tree = create_code_tree(&freq_table);
-    let symbol_table = create_code_table(&tree);
-    // calculate the bit size for the file block for compressed data and for tree
-    let mut char_count = 0;
-    for i in 0..TABLE_SIZE {
-        let freq = freq_table[i as usize];
-        block.data_bit_size += freq * (symbol_table[i as usize].bit_len as u64);
-        if freq > 0 {
-            char_count += 1;
+// compresses a buffer into a self describing stream: the byte size, the code table segment, then the data segment
+pub fn compress_bytes(bytes: &[u8]) -> Vec<u8> {
+    let mut output = vec![];
+    let mut writer = BitWriter::new(&mut output);
+    write_compressed_bytes(&mut writer, bytes, DEFAULT_MAX_CODE_LEN).expect("Writing to a vector can't fail");
+    drop(writer);
+
+    output
+}
+
+fn write_compressed_bytes<W: Write>(writer: &mut BitWriter<W>, bytes: &[u8], max_code_len: u8) -> io::Result<()> {
+    let freq_table = create_freq_table(bytes)?;
+    let tree = create_code_tree(&freq_table, max_code_len);
+    let symbol_table = create_code_table(&tree, TABLE_SIZE);
+
+    writer.write_u64(bytes.len() as u64)?;
+    write_code_lengths(writer, &symbol_table)?;
+    compress_file(bytes, writer, &symbol_table)?;
+    writer.flush()
+}
+
+// compresses everything written into it as a sequence of compress_bytes streams, one per block
+pub struct HuffmanEncoder<W: Write> {
+    // the stream compressed blocks are written to
+    writer: BitWriter<W>,
+    // bytes written since the last block was emitted
+    buffer: Vec<u8>,
+    // the byte size at which a block is emitted without waiting for a flush
+    block_size: usize,
+    // the longest code any symbol is given
+    max_code_len: u8
+}
+
+impl<W: Write> HuffmanEncoder<W> {
+    pub fn new(inner: W) -> HuffmanEncoder<W> {
+        HuffmanEncoder::with_block_size(DEFAULT_BLOCK_SIZE, inner)
+    }
+
+    pub fn with_block_size(block_size: usize, inner: W) -> HuffmanEncoder<W> {
+        HuffmanEncoder {
+            writer: BitWriter::new(inner),
+            buffer: vec![],
+            block_size: block_size.max(1),
+            max_code_len: DEFAULT_MAX_CODE_LEN
+        }
+    }
+
+    // limits the length of every code, up to the longest code a symbol code can hold
+    pub fn set_max_code_len(&mut self, max_code_len: u8) {
+        self.max_code_len = max_code_len.min(MAX_CODE_LEN);
+    }
+
+    pub fn get_ref(&self) -> &W {
+        self.writer.get_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut W {
+        self.writer.get_mut()
+    }
+
+    // emits any buffered bytes as a final block
+    pub fn finish(mut self) -> io::Result<()> {
+        self.emit_block()
+    }
+
+    fn emit_block(&mut self) -> io::Result<()> {
+        if !self.buffer.is_empty() {
+            write_compressed_bytes(&mut self.writer, &self.buffer, self.max_code_len)?;
+            self.buffer.clear();
         }
+        Ok(())
     }
-    block.tree_bit_size += 10 * char_count - 1;
-    // add the code book to file block
-    block.code_book = Some(CodeBook { symbol_table, tree });
 }
 
-fn write_block_headers(writer: &mut FileWriter, blocks: &mut [FileBlock]) {
-    // calculate the total block size for the header, including the grp sep byte
-    let mut header_size = 1;
-    for block in &*blocks {
-        // header size plus an additional rec sep byte
-        header_size += block.get_header_size() + 1;
+impl<W: Write> Write for HuffmanEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len().min(self.block_size - self.buffer.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if self.buffer.len() >= self.block_size {
+            self.emit_block()?;
+        }
+        Ok(len)
     }
-    // iterate through each block, calculate the file offset and write the block
-    let mut total_offset = 0;
-    for block in &mut *blocks {
-        // write record sep to identify start of record
-        writer.write_byte(REC_SEP);
-        // calculate the file sizes and offsets for the block
-        block.file_byte_offset = header_size + total_offset;
-        total_offset += 1 + (block.data_bit_size + block.tree_bit_size) / 8;
-        // write the block into memory
-        writer.write_block(block);
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.emit_block()?;
+        self.writer.flush()
     }
-    // write group sep after headers are complete
-    writer.write_byte(GRP_SEP);
 }
 
-fn compress_files(writer: &mut FileWriter, blocks: &[FileBlock]) {
-    for block in blocks {
-        let code_book = block.code_book.as_ref().unwrap();
-        write_node(writer, &code_book.tree.root);
-        compress_file(&block.filename_abs, writer, &code_book.symbol_table);
-        writer.align_to_byte();
+impl<W: Write> Drop for HuffmanEncoder<W> {
+    fn drop(&mut self) {
+        // errors can't be reported from drop, callers should finish to observe them
+        let _ = self.emit_block();
     }
 }
 
-fn write_node(writer: &mut FileWriter, node: &Box<Node>) {
-    if node.is_leaf() {
-        writer.write_bit(1);
-        writer.write_bits(node.plain_symbol, 8);
-    } else {
-        writer.write_bit(0);
-        let left = node.left.as_ref().expect("Expected left node to be Some");
-        write_node(writer, left);
-        let right = node.right.as_ref().expect("Expected right node to be Some");
-        write_node(writer, right);
+fn walk_path(base_path: &Path, path: &Path, blocks: &mut Vec<FileBlock>, links: &mut HashMap<(u64, u64), Vec<u8>>) -> Result<()> {
+    let filename_rel = path
+        .strip_prefix(base_path)
+        .map_err(|_| io::Error::new(ErrorKind::InvalidInput, "Couldn't strip prefix from path"))?;
+    let mut block = FileBlock::new(&utils::path_to_bytes(filename_rel), path);
+    // links are stored as links rather than followed
+    let metadata = fs::symlink_metadata(path)?;
+    read_file_metadata(&mut block, &metadata);
+
+    let file_type = metadata.file_type();
+    if file_type.is_dir() {
+        // the directory comes before its children so it exists when they are extracted
+        block.entry_type = EntryType::Directory;
+        blocks.push(block);
+        for entry in fs::read_dir(path)? {
+            let path = entry?.path();
+            walk_path(base_path, &path, blocks, links)?;
+        }
+    } else if file_type.is_symlink() {
+        block.entry_type = EntryType::Symlink;
+        block.link_target = utils::path_to_bytes(&fs::read_link(path)?);
+        blocks.push(block);
+    } else if file_type.is_file() {
+        // a file seen before under another name is stored as a link to the first name
+        if let Some(key) = hard_link_key(&metadata) {
+            if let Some(target) = links.get(&key) {
+                block.entry_type = EntryType::HardLink;
+                block.link_target = target.clone();
+            } else {
+                links.insert(key, block.filename_rel.clone());
+            }
+        }
+        if block.entry_type == EntryType::File {
+            block.original_byte_size = metadata.len();
+        }
+        blocks.push(block);
     }
+    // devices, fifos, and sockets have no contents to archive and are skipped
+    Ok(())
 }
 
-fn compress_file(input_filepath: &str, writer: &mut FileWriter, symbol_table: &[SymbolCode]) {
-    let mut reader = FileReader::new(input_filepath);
-    while !reader.eof() {
-        let byte = reader.read_byte();
-        writer.write_symbol(&symbol_table[byte as usize]);
+#[cfg(unix)]
+fn hard_link_key(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    if metadata.nlink() > 1 {
+        Some((metadata.dev(), metadata.ino()))
+    } else {
+        None
     }
 }
 
-fn create_freq_table(input_filepath: &str) -> Vec<u64> {
-    let mut freq_table = vec![0u64; TABLE_SIZE];
+// other platforms don't expose a stable file identity, so hard links are archived as copies
+#[cfg(not(unix))]
+fn hard_link_key(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
 
-    // iterate through each byte in the file and increment count
-    let mut reader = FileReader::new(input_filepath);
-    while !reader.eof() {
-        let byte = reader.read_byte();
-        freq_table[usize::from(byte)] += 1;
+#[cfg(unix)]
+fn read_file_metadata(block: &mut FileBlock, metadata: &fs::Metadata) {
+    use std::os::unix::fs::MetadataExt;
+    block.mode = metadata.mode();
+    block.mtime = metadata.mtime();
+    block.mtime_nsec = metadata.mtime_nsec() as u32;
+    block.uid = metadata.uid();
+    block.gid = metadata.gid();
+}
+
+// other platforms only have a modification time to preserve
+#[cfg(not(unix))]
+fn read_file_metadata(block: &mut FileBlock, metadata: &fs::Metadata) {
+    if let Ok(modified) = metadata.modified() {
+        (block.mtime, block.mtime_nsec) = utils::system_time_to_mtime(modified);
     }
+}
 
-    freq_table
+fn create_encoder(block: &mut FileBlock, codec: &dyn Codec) -> Result<()> {
+    // checksum the file in the same pass the codec reads it in
+    let mut input = ChecksumReader::new(File::open(&block.filename_abs)?);
+    let mut encoder = codec.create_encoder(&mut input)?;
+    block.method = codec.method();
+    block.checksum = input.checksum();
+    block.original_byte_size = input.byte_count();
+    let mut data_bit_size = match encoder.data_bit_size() {
+        Some(data_bit_size) => data_bit_size,
+        None => measure_data_bit_size(encoder.as_ref(), &block.filename_abs)?
+    };
+    // files that don't get any smaller are copied as they are
+    if encoder.header_bit_size() + data_bit_size > block.original_byte_size * 8 {
+        encoder = Box::new(StoredEncoder::new(block.original_byte_size));
+        block.method = METHOD_STORED;
+        data_bit_size = block.original_byte_size * 8;
+    }
+    block.tree_bit_size = encoder.header_bit_size();
+    block.data_bit_size = data_bit_size;
+    block.encoder = Some(encoder);
+    Ok(())
 }
 
-fn create_code_tree(freq_table: &[u64]) -> Tree {
-    let mut heap = BinaryHeap::new();
+// files with the same contents as an earlier file point at its compressed data rather than storing their own, returning
+// each duplicate's index with the index of the file it duplicates
+// only files whose size matches another file's are hashed, so archives without duplicates aren't read an extra time
+fn find_duplicates(blocks: &mut [FileBlock]) -> Result<Vec<(usize, usize)>> {
+    let mut size_counts: HashMap<u64, usize> = HashMap::new();
+    for block in blocks.iter().filter(|block| block.entry_type == EntryType::File) {
+        *size_counts.entry(block.original_byte_size).or_default() += 1;
+    }
+    let mut originals: HashMap<[u8; 32], usize> = HashMap::new();
+    let mut duplicates = vec![];
+    for index in 0..blocks.len() {
+        let block = &blocks[index];
+        // empty files have no data to share
+        if block.entry_type != EntryType::File || block.original_byte_size == 0 || size_counts[&block.original_byte_size] < 2 {
+            continue;
+        }
+        match originals.entry(hash_reader(File::open(&block.filename_abs)?)?) {
+            Entry::Occupied(entry) => {
+                let original = *entry.get();
+                blocks[index].link_target = blocks[original].filename_rel.clone();
+                duplicates.push((index, original));
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(index);
+            }
+        }
+    }
+    Ok(duplicates)
+}
 
-    // add the frequency table nodes to priority queue
-    let mut symbol_count = 0;
-    for i in 0..TABLE_SIZE {
-        let freq = freq_table[i];
-        if freq != 0 {
-            heap.push(Box::new(Node::leaf(i as u8, freq)));
-            symbol_count += 1;
+// counts the bytes of every file without its own method, clusters the files by their byte histograms, then gives each
+// of those files an encoder for the code table built from its cluster, returning the table of each cluster
+fn create_solid_encoders(
+    blocks: &mut [FileBlock],
+    entry_methods: &HashMap<usize, u8>,
+    max_code_len: u8,
+    code_book_count: u16
+) -> Result<Vec<Rc<[SymbolCode]>>> {
+    let mut indices = vec![];
+    let mut freq_tables = vec![];
+    for (index, block) in blocks.iter_mut().enumerate() {
+        if block.entry_type != EntryType::File || block.is_duplicate() || entry_methods.contains_key(&index) {
+            continue;
         }
+        let mut input = ChecksumReader::new(File::open(&block.filename_abs)?);
+        freq_tables.push(create_freq_table(&mut input)?);
+        indices.push(index);
+        block.checksum = input.checksum();
+        block.original_byte_size = input.byte_count();
     }
 
-    // huffman coding algorithm
-    while heap.len() > 1 {
-        let first_node = heap.pop().expect("First node is None");
-        let second_node = heap.pop().expect("Second node is None");
-        let w = first_node.weight + second_node.weight;
-        heap.push(Box::new(Node::internal(first_node, second_node, 0, w)));
+    // files their cluster's table would make larger, such as binaries among text, are left out and compressed on
+    // their own, the clusters and tables change without them so they are built again until every file left gets smaller
+    loop {
+        if freq_tables.is_empty() {
+            return Ok(vec![]);
+        }
+        let clusters = cluster_freq_tables(&freq_tables, code_book_count as usize);
+        let cluster_count = clusters.iter().max().expect("There is a cluster for each file") + 1;
+        let mut cluster_freq_tables = vec![vec![0u64; TABLE_SIZE]; cluster_count];
+        for (&cluster, freq_table) in clusters.iter().zip(&freq_tables) {
+            for (cluster_freq, freq) in cluster_freq_tables[cluster].iter_mut().zip(freq_table) {
+                *cluster_freq += freq;
+            }
+        }
+        let symbol_tables: Vec<Rc<[SymbolCode]>> = cluster_freq_tables
+            .iter()
+            .map(|freq_table| Rc::from(create_code_table(&create_code_tree(freq_table, max_code_len), TABLE_SIZE)))
+            .collect();
+        let encoders: Vec<SolidCodeBook> = clusters
+            .iter()
+            .zip(&freq_tables)
+            .map(|(&cluster, freq_table)| SolidCodeBook::new(symbol_tables[cluster].clone(), freq_table))
+            .collect();
+        if !encoders.iter().all(SolidCodeBook::shrinks) {
+            let shrinks: Vec<bool> = encoders.iter().map(SolidCodeBook::shrinks).collect();
+            let mut kept = shrinks.iter();
+            indices.retain(|_| *kept.next().expect("A flag for each file"));
+            let mut kept = shrinks.iter();
+            freq_tables.retain(|_| *kept.next().expect("A flag for each file"));
+            continue;
+        }
+
+        for ((index, cluster), encoder) in indices.into_iter().zip(clusters).zip(encoders) {
+            let block = &mut blocks[index];
+            block.method = METHOD_SOLID;
+            block.code_book = cluster as u16;
+            block.tree_bit_size = 0;
+            block.data_bit_size = encoder.data_bit_size().expect("Solid code books know their size");
+            block.encoder = Some(Box::new(encoder));
+        }
+        return Ok(symbol_tables);
     }
+}
 
-    let root = heap.pop().expect("Heap is empty after algorithm");
-    Tree { root, symbol_count }
+// the bits ahead of the solid stream, the number of code tables when there are several then each table
+fn solid_tables_bit_size(symbol_tables: &[Rc<[SymbolCode]>]) -> u64 {
+    let count_bit_size = if symbol_tables.len() > 1 { 16 } else { 0 };
+    count_bit_size + symbol_tables.iter().map(|symbol_table| code_lengths_bit_size(symbol_table)).sum::<u64>()
 }
 
-fn walk_code_tree(node: &Box<Node>, mut symbol_code: SymbolCode, symbol_table: &mut [SymbolCode]) {
-    if node.is_leaf() {
-        symbol_code.plain_symbol = node.plain_symbol;
-        symbol_table[usize::from(node.plain_symbol)] = symbol_code;
+// encodes the file into nothing, for encoders that only know the size of their data once it is encoded
+fn measure_data_bit_size(encoder: &dyn EntryEncoder, filepath: &Path) -> io::Result<u64> {
+    let mut writer = BitWriter::new(io::sink());
+    encoder.encode(&mut File::open(filepath)?, &mut writer)?;
+    Ok(writer.write_len() - encoder.header_bit_size())
+}
+
+fn write_block_headers<W: Write>(
+    writer: &mut BitWriter<W>,
+    blocks: &mut [FileBlock],
+    duplicates: &[(usize, usize)],
+    solid_tables: &[Rc<[SymbolCode]>]
+) -> Result<()> {
+    // calculate the total block size for the header, including the block count
+    let mut header_size = get_size_of(blocks.len() as u64) as u64;
+    for block in &*blocks {
+        header_size += block.get_header_size();
+    }
+    // write the block count so the reader knows where the header segment ends
+    writer.write_u64(blocks.len() as u64)?;
+    // the solid stream comes first, starting with its code tables, and each file in it starts where the one before ends
+    let mut total_offset = 0;
+    if !solid_tables.is_empty() {
+        let mut bit_offset = solid_tables_bit_size(solid_tables);
+        for block in blocks.iter_mut().filter(|block| block.method == METHOD_SOLID && !block.is_duplicate()) {
+            block.file_byte_offset = header_size + bit_offset / 8;
+            block.bit_offset = (bit_offset % 8) as u8;
+            bit_offset += block.data_bit_size;
+        }
+        total_offset = bit_offset.div_ceil(8);
+    }
+    // calculate the file offset of each other block, each block is padded to a byte
+    for block in blocks.iter_mut().filter(|block| block.method != METHOD_SOLID && !block.is_duplicate()) {
+        block.file_byte_offset = header_size + total_offset;
+        total_offset += (block.data_bit_size + block.tree_bit_size).div_ceil(8);
     }
-    if let Some(left) = &node.left {
-        let symbol_code = symbol_code.append_bit(0);
-        walk_code_tree(left, symbol_code, symbol_table);
+    // duplicates are decoded from the same data as the file they duplicate, so they take on how it was stored
+    for &(duplicate, original) in duplicates {
+        // the file a duplicate duplicates always comes before it
+        let (before, after) = blocks.split_at_mut(duplicate);
+        let (original, block) = (&before[original], &mut after[0]);
+        block.method = original.method;
+        block.tree_bit_size = original.tree_bit_size;
+        block.data_bit_size = original.data_bit_size;
+        block.file_byte_offset = original.file_byte_offset;
+        block.bit_offset = original.bit_offset;
+        block.code_book = original.code_book;
+        block.original_byte_size = original.original_byte_size;
+        block.checksum = original.checksum;
     }
-    if let Some(right) = &node.right {
-        let symbol_code = symbol_code.append_bit(1);
-        walk_code_tree(right, symbol_code, symbol_table);
+    for block in &*blocks {
+        writer.write_block(block)?;
     }
+    Ok(())
 }
 
-fn create_code_table(tree: &Tree) -> Vec<SymbolCode> {
-    let symbol_code = SymbolCode::new();
-    let mut symbol_table = vec![symbol_code; TABLE_SIZE];
-    walk_code_tree(&tree.root, symbol_code, &mut symbol_table);
-    symbol_table
-}
\ No newline at end of file
+fn compress_files<W: Write>(writer: &mut BitWriter<W>, blocks: &[FileBlock], solid_tables: &[Rc<[SymbolCode]>]) -> Result<()> {
+    if !solid_tables.is_empty() {
+        if solid_tables.len() > 1 {
+            writer.write_u16(solid_tables.len() as u16)?;
+        }
+        for symbol_table in solid_tables {
+            write_code_lengths(writer, symbol_table)?;
+        }
+        for block in blocks.iter().filter(|block| block.method == METHOD_SOLID && !block.is_duplicate()) {
+            let encoder = block.encoder.as_ref().expect("Encoders are created before compressing");
+            encoder.encode(&mut File::open(&block.filename_abs)?, writer)?;
+        }
+        writer.align_to_byte();
+    }
+    for block in blocks.iter().filter(|block| block.entry_type == EntryType::File && block.method != METHOD_SOLID && !block.is_duplicate()) {
+        let encoder = block.encoder.as_ref().expect("Encoders are created before compressing");
+        encoder.encode(&mut File::open(&block.filename_abs)?, writer)?;
+        writer.align_to_byte();
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::PathBuf;
+    use std::{env, process};
+    use crate::decompress::{decompress_bytes, HuffmanDecoder};
+    use crate::error::ZipperError;
+    use super::*;
+
+    // bytes from a fixed xorshift generator, so any failure reproduces
+    pub(crate) fn noise(len: usize) -> Vec<u8> {
+        let mut state = 0x2545F4914F6CDD1Du64;
+        (0..len).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect()
+    }
+
+    // an empty directory named after the test, so tests running at the same time don't share one
+    pub(crate) fn temp_dir(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("zipper-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    // archives the file or directory into memory
+    pub(crate) fn archive_in_memory(entry: &Path) -> Vec<u8> {
+        let mut archive = vec![];
+        let mut writer = ArchiveWriter::new(&mut archive);
+        writer.add_entry(entry).unwrap();
+        writer.finish().unwrap();
+        archive
+    }
+
+    #[test]
+    fn buffers_round_trip() {
+        for input in [vec![], vec![b'a'], b"abracadabra".repeat(50), noise(10_000)] {
+            assert_eq!(decompress_bytes(&compress_bytes(&input)).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn truncated_or_garbage_streams_fail() {
+        let compressed = compress_bytes(&b"abracadabra".repeat(50));
+        assert!(matches!(decompress_bytes(&compressed[..4]), Err(ZipperError::TruncatedHeader)));
+        assert!(decompress_bytes(&compressed[..compressed.len() / 2]).is_err());
+        // a size with a tree that is only a leaf
+        assert!(matches!(decompress_bytes(&[0xFF; 64]), Err(ZipperError::MalformedTree)));
+    }
+
+    #[test]
+    fn encoder_emits_a_block_on_every_flush() {
+        let mut encoded = vec![];
+        let mut encoder = HuffmanEncoder::new(&mut encoded);
+        encoder.write_all(b"hello ").unwrap();
+        encoder.flush().unwrap();
+        // a flush with nothing buffered emits no empty block
+        encoder.flush().unwrap();
+        encoder.write_all(b"world").unwrap();
+        encoder.finish().unwrap();
+        assert_eq!(encoded, [compress_bytes(b"hello "), compress_bytes(b"world")].concat());
+    }
+
+    #[test]
+    fn encoder_emits_a_block_at_its_block_size() {
+        let input = noise(250);
+        let mut encoded = vec![];
+        let mut encoder = HuffmanEncoder::with_block_size(100, &mut encoded);
+        encoder.write_all(&input).unwrap();
+        encoder.finish().unwrap();
+        let blocks: Vec<Vec<u8>> = input.chunks(100).map(compress_bytes).collect();
+        assert_eq!(encoded, blocks.concat());
+    }
+
+    #[test]
+    fn streams_round_trip_through_io_copy() {
+        let input = b"abracadabra".repeat(1000);
+        let mut encoded = vec![];
+        let mut encoder = HuffmanEncoder::with_block_size(4096, &mut encoded);
+        io::copy(&mut input.as_slice(), &mut encoder).unwrap();
+        encoder.finish().unwrap();
+
+        let mut decoded = vec![];
+        io::copy(&mut HuffmanDecoder::new(encoded.as_slice()), &mut decoded).unwrap();
+        assert_eq!(decoded, input);
+    }
+}
@@ -0,0 +1,316 @@
+// Joseph Prichard
+// 10/18/2026
+// Order-1 context huffman codec, coding each byte with a code table picked by the byte before it
+
+use std::io;
+use std::io::{Read, Write};
+use crate::bitwise::SymbolCode;
+use crate::block::FileBlock;
+use crate::canonical::{DecodeTable, MAX_CODE_LEN};
+use crate::codec::{Codec, EntryDecoder, EntryEncoder, METHOD_CONTEXT};
+use crate::error::{Result, ZipperError};
+use crate::huffman;
+use crate::huffman::{tree_error, DEFAULT_MAX_CODE_LEN, TABLE_SIZE};
+use crate::read::BitReader;
+use crate::write::BitWriter;
+
+// the bit width of the number of contexts with their own code table, from none to every byte
+const CONTEXT_COUNT_WIDTH: u8 = 9;
+// the table index for the shared table, used by the first byte and every context without its own table
+const SHARED_TABLE: usize = 0;
+// the rounds of picking contexts, as the shared table changes with the contexts that are left to it
+const SELECTION_ROUNDS: usize = 2;
+
+pub struct ContextCodec {
+    // the longest code any symbol is given
+    max_code_len: u8
+}
+
+impl ContextCodec {
+    // limits the length of every code, up to the longest code a symbol code can hold
+    pub fn new(max_code_len: u8) -> ContextCodec {
+        ContextCodec { max_code_len: max_code_len.min(MAX_CODE_LEN) }
+    }
+
+    fn create_symbol_table(&self, freq_table: &[u64]) -> Vec<SymbolCode> {
+        let tree = huffman::create_code_tree(freq_table, self.max_code_len);
+        huffman::create_code_table(&tree, TABLE_SIZE)
+    }
+}
+
+impl Default for ContextCodec {
+    fn default() -> ContextCodec {
+        ContextCodec::new(DEFAULT_MAX_CODE_LEN)
+    }
+}
+
+impl Codec for ContextCodec {
+    fn method(&self) -> u8 {
+        METHOD_CONTEXT
+    }
+
+    fn name(&self) -> &'static str {
+        "context"
+    }
+
+    fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>> {
+        // count each byte under the byte before it, the first byte has no context and always uses the shared table
+        let mut context_freq_tables = vec![vec![0u64; TABLE_SIZE]; TABLE_SIZE];
+        let mut first_freq_table = vec![0u64; TABLE_SIZE];
+        let mut reader = BitReader::new(input);
+        let mut prev_byte = None;
+        while !reader.eof()? {
+            let byte = reader.read_byte()?;
+            match prev_byte {
+                Some(prev_byte) => context_freq_tables[prev_byte as usize][byte as usize] += 1,
+                None => first_freq_table[byte as usize] += 1
+            }
+            prev_byte = Some(byte);
+        }
+
+        // a context gets its own table when that costs fewer bits, table included, than the shared table
+        // the shared table is rebuilt from the contexts left to it, so the choice is made again against it
+        let mut own_table = vec![false; TABLE_SIZE];
+        let mut shared_table = vec![];
+        for _ in 0..SELECTION_ROUNDS {
+            let mut shared_freq_table = first_freq_table.clone();
+            for (context, freq_table) in context_freq_tables.iter().enumerate() {
+                if !own_table[context] {
+                    add_freqs(&mut shared_freq_table, freq_table);
+                }
+            }
+            shared_table = self.create_symbol_table(&shared_freq_table);
+            for (context, freq_table) in context_freq_tables.iter().enumerate() {
+                // bytes the shared table has no code for have to get a table of their own
+                let shared_cost = coded_bit_size(freq_table, &shared_table).unwrap_or(u64::MAX);
+                let table = self.create_symbol_table(freq_table);
+                let own_cost = huffman::code_lengths_bit_size(&table) + coded_bit_size(freq_table, &table).unwrap_or(0);
+                own_table[context] = own_cost < shared_cost;
+            }
+        }
+
+        // the shared table from the last round covers every context left to it
+        let mut table_of_context = vec![SHARED_TABLE; TABLE_SIZE];
+        let mut symbol_tables = vec![shared_table];
+        let mut contexts = vec![];
+        let mut data_bit_size = coded_bit_size(&first_freq_table, &symbol_tables[SHARED_TABLE]).unwrap_or(0);
+        for (context, freq_table) in context_freq_tables.iter().enumerate() {
+            if own_table[context] {
+                contexts.push(context);
+                table_of_context[context] = symbol_tables.len();
+                symbol_tables.push(self.create_symbol_table(freq_table));
+            }
+            data_bit_size += coded_bit_size(freq_table, &symbol_tables[table_of_context[context]]).unwrap_or(0);
+        }
+
+        let byte_size = first_freq_table.iter().chain(context_freq_tables.iter().flatten()).sum();
+        Ok(Box::new(ContextEncoder {
+            contexts,
+            table_of_context,
+            symbol_tables,
+            data_bit_size,
+            byte_size
+        }))
+    }
+
+    fn create_decoder(&self, reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>> {
+        let context_count = reader.read_bits_u32(CONTEXT_COUNT_WIDTH).map_err(tree_error)? as usize;
+        if context_count > TABLE_SIZE {
+            return Err(ZipperError::MalformedTree);
+        }
+        let mut table_of_context = vec![SHARED_TABLE; TABLE_SIZE];
+        let mut next_context = 0;
+        for table in 1..=context_count {
+            let context = next_context + huffman::read_gamma(reader, TABLE_SIZE)? as usize - 1;
+            if context >= TABLE_SIZE {
+                return Err(ZipperError::MalformedTree);
+            }
+            table_of_context[context] = table;
+            next_context = context + 1;
+        }
+        let mut tables = vec![];
+        for _ in 0..=context_count {
+            tables.push(huffman::read_code_lengths(reader, TABLE_SIZE)?);
+        }
+        Ok(Box::new(ContextDecoder {
+            table_of_context,
+            tables,
+            prev_byte: None,
+            remaining: block.original_byte_size
+        }))
+    }
+}
+
+fn add_freqs(freq_table: &mut [u64], other: &[u64]) {
+    for (freq, other_freq) in freq_table.iter_mut().zip(other) {
+        *freq += other_freq;
+    }
+}
+
+// the bit size of the bytes coded with the table, or none if the table has no code for one of them
+fn coded_bit_size(freq_table: &[u64], symbol_table: &[SymbolCode]) -> Option<u64> {
+    let mut bit_size = 0;
+    for (&freq, symbol_code) in freq_table.iter().zip(symbol_table) {
+        if freq > 0 && symbol_code.bit_len == 0 {
+            return None;
+        }
+        bit_size += freq * symbol_code.bit_len as u64;
+    }
+    Some(bit_size)
+}
+
+struct ContextEncoder {
+    // the contexts that have their own table, in order
+    contexts: Vec<usize>,
+    // the index of the table each context is coded with
+    table_of_context: Vec<usize>,
+    // the shared table followed by the table of each context in order
+    symbol_tables: Vec<Vec<SymbolCode>>,
+    // the bit size of the file once compressed
+    data_bit_size: u64,
+    // the byte size of the file the codes were built for
+    byte_size: u64
+}
+
+impl EntryEncoder for ContextEncoder {
+    fn header_bit_size(&self) -> u64 {
+        let mut bit_size = CONTEXT_COUNT_WIDTH as u64;
+        let mut prev_context = 0;
+        for &context in &self.contexts {
+            bit_size += huffman::gamma_bit_size((context + 1 - prev_context) as u32);
+            prev_context = context + 1;
+        }
+        bit_size + self.symbol_tables.iter().map(|symbol_table| huffman::code_lengths_bit_size(symbol_table)).sum::<u64>()
+    }
+
+    fn data_bit_size(&self) -> Option<u64> {
+        Some(self.data_bit_size)
+    }
+
+    // the contexts with their own table are stored as gaps like the symbols of a code table, then every table
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()> {
+        writer.write_bits_u32(self.contexts.len() as u32, CONTEXT_COUNT_WIDTH)?;
+        let mut prev_context = 0;
+        for &context in &self.contexts {
+            huffman::write_gamma(writer, (context + 1 - prev_context) as u32)?;
+            prev_context = context + 1;
+        }
+        for symbol_table in &self.symbol_tables {
+            huffman::write_code_lengths(writer, symbol_table)?;
+        }
+
+        // stop at the size the codes were built for in case the file has grown since
+        let mut reader = BitReader::new(input.take(self.byte_size));
+        let mut table = SHARED_TABLE;
+        while !reader.eof()? {
+            let byte = reader.read_byte()?;
+            writer.write_symbol(&self.symbol_tables[table][byte as usize])?;
+            table = self.table_of_context[byte as usize];
+        }
+        Ok(())
+    }
+}
+
+struct ContextDecoder {
+    table_of_context: Vec<usize>,
+    tables: Vec<DecodeTable>,
+    prev_byte: Option<u8>,
+    // the number of bytes left to decode
+    remaining: u64
+}
+
+impl EntryDecoder for ContextDecoder {
+    fn decode(&mut self, reader: &mut BitReader<dyn Read + '_>, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() && self.remaining > 0 {
+            let table = match self.prev_byte {
+                Some(prev_byte) => self.table_of_context[prev_byte as usize],
+                None => SHARED_TABLE
+            };
+            let byte = self.tables[table].decode_next_symbol(reader)? as u8;
+            self.prev_byte = Some(byte);
+            buf[len] = byte;
+            self.remaining -= 1;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::compress::tests::noise;
+    use super::*;
+
+    // encodes the input, checking the encoder wrote the sizes it reported
+    fn encode(input: &[u8]) -> (Vec<u8>, FileBlock) {
+        let encoder = ContextCodec::default().create_encoder(&mut &input[..]).unwrap();
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        encoder.encode(&mut &input[..], &mut writer).unwrap();
+        assert_eq!(writer.write_len(), encoder.header_bit_size() + encoder.data_bit_size().unwrap());
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut block = FileBlock::new(b"entry", Path::new(""));
+        block.original_byte_size = input.len() as u64;
+        block.tree_bit_size = encoder.header_bit_size();
+        block.data_bit_size = encoder.data_bit_size().unwrap();
+        (bytes, block)
+    }
+
+    fn decode(bytes: &[u8], block: &FileBlock) -> Result<Vec<u8>> {
+        let mut reader = BitReader::new(bytes);
+        let mut decoder = ContextCodec::default().create_decoder(&mut reader, block)?;
+        let mut decoded = vec![0; block.original_byte_size as usize];
+        let mut len = 0;
+        while len < decoded.len() {
+            len += decoder.decode(&mut reader, &mut decoded[len..])?;
+        }
+        Ok(decoded)
+    }
+
+    #[test]
+    fn predictable_contexts_get_their_own_table() {
+        // each byte always follows the same byte, so every context has only one byte to code
+        let input = b"abc".repeat(1000);
+        let (bytes, block) = encode(&input);
+        // once the shared table is left with only 'a' and 'c', it codes both in one bit, so only 'a' needs its own table for 'b'
+        let mut reader = BitReader::new(bytes.as_slice());
+        assert_eq!(reader.read_bits_u32(CONTEXT_COUNT_WIDTH).unwrap(), 1);
+        assert_eq!(huffman::read_gamma(&mut reader, TABLE_SIZE).unwrap(), b'a' as u32 + 1);
+        assert_eq!(block.data_bit_size, input.len() as u64);
+        assert_eq!(decode(&bytes, &block).unwrap(), input);
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let text = b"the quick brown fox jumps over the lazy dog. ".repeat(100);
+        for input in [vec![], vec![b'a'], text, noise(5000)] {
+            let (bytes, block) = encode(&input);
+            assert_eq!(decode(&bytes, &block).unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn contexts_out_of_range_are_refused() {
+        let block = FileBlock::new(b"entry", Path::new(""));
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_bits_u32(TABLE_SIZE as u32 + 1, CONTEXT_COUNT_WIDTH).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert!(matches!(decode(&bytes, &block), Err(ZipperError::MalformedTree)));
+
+        // a gap past the last byte
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_bits_u32(2, CONTEXT_COUNT_WIDTH).unwrap();
+        huffman::write_gamma(&mut writer, 200).unwrap();
+        huffman::write_gamma(&mut writer, 100).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert!(matches!(decode(&bytes, &block), Err(ZipperError::MalformedTree)));
+    }
+}
@@ -2,48 +2,53 @@
 // 1/5/2023
 // Utilities used for debugging only
 
+use std::io;
 use crate::bitwise::SymbolCode;
 use crate::read::FileReader;
-use crate::tree::Node;
+use crate::tree::Tree;
 
-pub fn debug_binary_file(filepath: &str) {
-    let mut reader = FileReader::new(filepath);
+pub fn debug_binary_file(filepath: &str) -> io::Result<()> {
+    let mut reader = FileReader::open(filepath)?;
     println!();
     let mut c = 0;
-    while !reader.eof() {
-        let bit = reader.read_bit();
+    while !reader.eof()? {
+        let bit = reader.read_bit()?;
         print!("{}", bit);
         if (c + 1) % 4 == 0 {
             print!(" ");
         }
         c += 1;
     }
+    Ok(())
 }
 
-pub fn debug_tree_file(filepath: &str) {
-    let mut reader = FileReader::new(filepath);
+pub fn debug_tree_file(filepath: &str) -> io::Result<()> {
+    let mut reader = FileReader::open(filepath)?;
     println!();
-    while !reader.eof() {
-        let bit = reader.read_bit();
+    while !reader.eof()? {
+        let bit = reader.read_bit()?;
         print!("{}", bit);
         if bit > 0 {
-            let byte = reader.read_bits(8);
+            let byte = reader.read_bits(8)?;
             print!("{}", byte as char);
         }
     }
+    Ok(())
 }
 
-pub fn debug_tree(node: &Box<Node>, symbol_code: SymbolCode) {
+pub fn debug_tree(tree: &Tree, index: usize, symbol_code: SymbolCode) {
+    let node = &tree.nodes[index];
     if node.is_leaf() {
-        println!("Leaf: {:#b} {} {}", symbol_code.encoded_symbol, symbol_code.bit_len, node.plain_symbol as char);
+        let symbol = char::from_u32(node.plain_symbol as u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+        println!("Leaf: {:#b} {} {}", symbol_code.encoded_symbol, symbol_code.bit_len, symbol);
     }
-    if let Some(left) = &node.left {
+    if let Some(left) = node.left {
         let symbol_code = symbol_code.append_bit(0);
-        debug_tree(left, symbol_code);
+        debug_tree(tree, left, symbol_code);
     }
-    if let Some(right) = &node.right {
+    if let Some(right) = node.right {
         let symbol_code = symbol_code.append_bit(1);
-        debug_tree(right, symbol_code);
+        debug_tree(tree, right, symbol_code);
     }
 }
 
@@ -2,101 +2,971 @@
 // 1/5/2023
 // Bit-by-bit file decompressor
 
-use std::fs;
-use std::path;
+use std::{fs, io};
+use std::fs::File;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
 use std::path::{Path};
-use std::time::Instant;
-use crate::block::{FileBlock};
-use crate::charset::{GRP_SEP, SIG};
-use crate::debug::debug_tree;
-use crate::read::FileReader;
-use crate::tree::Node;
-use crate::utils;
-use crate::utils::{get_parent_name, get_size_of};
-use crate::write::FileWriter;
-
-pub fn unarchive_zip(input_filepath: &str) {
-    let now = Instant::now();
-
-    let output_dir = utils::get_no_ext(input_filepath);
-    fs::create_dir_all(&output_dir).expect("Couldn't create directory");
-    let blocks = get_file_blocks(input_filepath);
-    decompress_files(&blocks, input_filepath, &output_dir);
-
-    let elapsed = now.elapsed();
-    println!("Finished unzipping in {:.2?}", elapsed);
-}
-
-pub fn get_file_blocks(archive_filepath: &str) -> Vec<FileBlock> {
-    let mut reader = FileReader::new(archive_filepath);
-    if reader.read_u64() != SIG {
-        panic!("File is not a zipr file");
-    }
-    // iterate through headers until the file separator byte is found or eof
-    let mut blocks = vec![];
-    while !reader.eof() {
-        let sep = reader.read_byte();
-        if sep == GRP_SEP {
-            break;
+use crate::block::{ArchiveHeader, EntryType, FileBlock};
+use crate::checksum::Crc32;
+use crate::charset::{FLAG_CODE_BOOKS, FLAG_SOLID, REQUIRED_FLAGS_MASK, SUPPORTED_FLAGS, VERSION_MAJOR};
+use crate::error::{Result, ZipperError};
+use crate::read::{header_error, BitReader};
+use crate::canonical::DecodeTable;
+use crate::codec::{Codec, CodecRegistry, EntryDecoder, METHOD_SOLID};
+use crate::dictionary::{Dictionary, DictionaryCodec};
+use crate::huffman::{read_code_lengths, HuffmanEntryDecoder, TABLE_SIZE};
+use crate::utils::{mtime_to_system_time, path_to_bytes};
+
+// reads the blocks stored in an existing archive, giving random access to each entry
+pub struct ArchiveReader<R: Read + Seek> {
+    // the stream the archive is read from
+    reader: BitReader<R>,
+    // the version and flags the archive was written with
+    header: ArchiveHeader,
+    // the blocks parsed from the archive header
+    blocks: Vec<FileBlock>,
+    // the total byte size of the archive
+    archive_len: u64,
+    // the codecs entries can be decoded with
+    codecs: CodecRegistry,
+    // the code tables shared by entries in the solid stream, empty for archives without one
+    solid_tables: Vec<DecodeTable>
+}
+
+impl ArchiveReader<File> {
+    pub fn open<P: AsRef<Path>>(archive_filepath: P) -> Result<ArchiveReader<File>> {
+        ArchiveReader::new(File::open(archive_filepath)?)
+    }
+}
+
+impl<R: Read + Seek> ArchiveReader<R> {
+    pub fn new(mut inner: R) -> Result<ArchiveReader<R>> {
+        let archive_len = inner.seek(SeekFrom::End(0))?;
+        inner.seek(SeekFrom::Start(0))?;
+        let mut reader = BitReader::new(inner);
+        let header = get_archive_header(&mut reader)?;
+        let blocks = get_file_blocks(&mut reader)?;
+        let solid_tables = get_solid_tables(&mut reader, &header)?;
+        Ok(ArchiveReader { reader, header, blocks, archive_len, codecs: CodecRegistry::default(), solid_tables })
+    }
+
+    // adds a codec for decoding entries, replacing any codec with the same method id
+    pub fn register_codec(&mut self, codec: Box<dyn Codec>) {
+        self.codecs.register(codec);
+    }
+
+    // decodes entries compressed with the trained dictionary, entries coded with another dictionary still fail
+    pub fn set_dictionary(&mut self, dictionary: Dictionary) {
+        self.codecs.register(Box::new(DictionaryCodec::new(dictionary)));
+    }
+
+    pub fn header(&self) -> &ArchiveHeader {
+        &self.header
+    }
+
+    pub fn entries(&self) -> &[FileBlock] {
+        &self.blocks
+    }
+
+    // opens the entry stored under the relative path, decoding it as it is read
+    pub fn by_name<P: AsRef<Path>>(&mut self, filename_rel: P) -> Result<EntryReader<'_, R>> {
+        let filename_rel = filename_rel.as_ref();
+        let filename_bytes = path_to_bytes(filename_rel);
+        let index = self.blocks
+            .iter()
+            .position(|block| block.filename_rel == filename_bytes)
+            .ok_or_else(|| ZipperError::EntryNotFound(filename_rel.display().to_string()))?;
+        self.by_index(index)
+    }
+
+    // opens the entry at the position in the archive header, decoding it as it is read
+    // hard links decode the file they link to, while directories and symlinks have no contents
+    pub fn by_index(&mut self, index: usize) -> Result<EntryReader<'_, R>> {
+        let mut block = self.blocks
+            .get(index)
+            .ok_or_else(|| ZipperError::EntryNotFound(format!("#{}", index)))?;
+        if block.entry_type == EntryType::HardLink {
+            block = self.blocks
+                .iter()
+                .find(|target| target.entry_type == EntryType::File && target.filename_rel == block.link_target)
+                .ok_or_else(|| ZipperError::EntryNotFound(String::from_utf8_lossy(&block.link_target).into_owned()))?;
+        }
+        if block.entry_type != EntryType::File {
+            return Ok(EntryReader {
+                reader: &mut self.reader,
+                block,
+                decoder: None,
+                data_bit_offset: 0,
+                position: 0,
+                crc: Some(Crc32::new())
+            });
+        }
+
+        // check the compressed data for the block lies within the archive before jumping to it
+        // the sizes come from the archive, so sums that overflow point past its end too
+        let bad_offset = || ZipperError::BadOffset(block.file_byte_offset);
+        let data_byte_offset = self.header.get_header_size().checked_add(block.file_byte_offset).ok_or_else(bad_offset)?;
+        let data_bit_size = (block.bit_offset as u64)
+            .checked_add(block.tree_bit_size)
+            .and_then(|bit_size| bit_size.checked_add(block.data_bit_size))
+            .ok_or_else(bad_offset)?;
+        let data_byte_end = data_byte_offset.checked_add(data_bit_size.div_ceil(8)).ok_or_else(bad_offset)?;
+        if data_byte_end > self.archive_len {
+            return Err(bad_offset());
+        }
+        // the bit the entry starts at, and the bit its data starts at after whatever the codec stored in front of it
+        let entry_bit_offset = data_byte_offset
+            .checked_mul(8)
+            .and_then(|bit_offset| bit_offset.checked_add(block.bit_offset as u64))
+            .ok_or_else(bad_offset)?;
+        let data_bit_offset = entry_bit_offset.checked_add(block.tree_bit_size).ok_or_else(bad_offset)?;
+
+        // jump to the data segment, entries in the solid stream can start mid byte
+        let reader = &mut self.reader;
+        reader.seek_from_start(data_byte_offset)?;
+        reader.read_bits(block.bit_offset)?;
+        // entries in the solid stream have no header and are decoded with the shared code table
+        if block.method == METHOD_SOLID {
+            let table = self.solid_tables.get(block.code_book as usize).ok_or(ZipperError::MalformedTree)?.clone();
+            return Ok(EntryReader {
+                reader,
+                block,
+                decoder: Some(Box::new(HuffmanEntryDecoder::new(table, block.original_byte_size))),
+                data_bit_offset: entry_bit_offset,
+                position: 0,
+                crc: Some(Crc32::new())
+            });
         }
-        let block = reader.read_block();
-        blocks.push(block);
+
+        // let the codec read whatever it stored in front of the compressed data
+        let codec = self.codecs
+            .get(block.method)
+            .ok_or(ZipperError::UnsupportedMethod(block.method))?;
+        let start_read_len = reader.read_len();
+        let decoder = codec.create_decoder(reader, block)?;
+        if reader.read_len() - start_read_len != block.tree_bit_size {
+            return Err(ZipperError::MalformedTree);
+        }
+
+        Ok(EntryReader {
+            reader,
+            block,
+            decoder: Some(decoder),
+            data_bit_offset,
+            position: 0,
+            crc: Some(Crc32::new())
+        })
+    }
+
+    // decompresses every block in the archive into the output directory
+    pub fn extract_all<P: AsRef<Path>>(&mut self, output_dir: P) -> Result<()> {
+        let output_dir = output_dir.as_ref();
+        fs::create_dir_all(output_dir)?;
+        decompress_files(self, output_dir)
+    }
+
+    // the bit size the entry would take huffman coded, for entries whose codec stores it
+    // entries of other codecs aren't opened, so listing them only reads the archive header
+    pub fn huffman_bit_size(&mut self, index: usize) -> Result<Option<u64>> {
+        let block = self.blocks
+            .get(index)
+            .ok_or_else(|| ZipperError::EntryNotFound(format!("#{}", index)))?;
+        let stores_huffman_bit_size = block.entry_type == EntryType::File && self.codecs
+            .get(block.method)
+            .is_some_and(|codec| codec.stores_huffman_bit_size());
+        if !stores_huffman_bit_size {
+            return Ok(None);
+        }
+        Ok(self.by_index(index)?.huffman_bit_size())
+    }
+
+    // decodes an entry without writing it anywhere, failing if its checksum doesn't match
+    pub fn test_entry(&mut self, index: usize) -> Result<()> {
+        let mut entry = self.by_index(index)?;
+        io::copy(&mut entry, &mut io::sink())?;
+        Ok(())
     }
-    blocks
 }
 
-fn decompress_files(blocks: &[FileBlock], archive_filepath:&str, output_dir: &str) {
-    for block in blocks {
-        decompress_file(&block, output_dir, archive_filepath);
+// decodes the compressed data of a single entry in an archive
+pub struct EntryReader<'a, R: Read + Seek> {
+    // the archive stream, positioned inside the entry's data segment
+    reader: &'a mut BitReader<R>,
+    // the block being decoded
+    block: &'a FileBlock,
+    // the decoder for the entry's codec, missing for entries without contents
+    decoder: Option<Box<dyn EntryDecoder>>,
+    // the bit position in the archive where the compressed data starts, after the codec's header
+    data_bit_offset: u64,
+    // the number of bytes of the entry read or skipped so far
+    position: u64,
+    // checksum of the bytes decoded so far, verified once the data segment is exhausted
+    // there is nothing to verify once part of the entry has been skipped by seeking
+    crc: Option<Crc32>
+}
+
+impl<R: Read + Seek> EntryReader<'_, R> {
+    pub fn block(&self) -> &FileBlock {
+        self.block
+    }
+
+    pub fn huffman_bit_size(&self) -> Option<u64> {
+        self.decoder.as_ref().and_then(|decoder| decoder.huffman_bit_size())
     }
 }
 
-fn decompress_file(block: &FileBlock, output_dir: &str, archive_filepath: &str) {
-    let unarchived_filename = &format!("{}{}{}", output_dir, path::MAIN_SEPARATOR, &block.filename_rel);
+impl<R: Read + Seek> Read for EntryReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = match &mut self.decoder {
+            Some(decoder) => decoder.decode(self.reader, buf)?,
+            None => 0
+        };
+        self.position += len as u64;
+        let Some(crc) = &mut self.crc else {
+            return Ok(len);
+        };
+        crc.update(&buf[..len]);
 
-    // read from the main archive jumping to the data segment
-    let reader = &mut FileReader::new(archive_filepath);
-    reader.seek_from_start((get_size_of(SIG) as u64) + block.file_byte_offset);
+        // reaching the end of the entry is the only point the checksum can be verified
+        if len == 0 && !buf.is_empty() && crc.checksum() != self.block.checksum {
+            let name = self.block.display_name().into_owned();
+            return Err(ZipperError::ChecksumMismatch(name).into());
+        }
+        Ok(len)
+    }
+}
 
-    let root = read_node(reader);
+// seeking jumps to the chunk holding the position for codecs with independent chunks, then decodes up to it
+// other codecs can only seek forward, decoding and discarding the bytes in between
+impl<R: Read + Seek> Seek for EntryReader<'_, R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.block.original_byte_size.checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.position.checked_add_signed(offset)
+        }.ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "Seek to a position before the start of the entry"))?;
+        if target == self.position {
+            return Ok(target);
+        }
+        self.crc = None;
 
-    let unarchived_parent = Path::new(unarchived_filename).parent().unwrap();
-    fs::create_dir_all(unarchived_parent).expect("Couldn't create directories");
+        let Some(decoder) = &mut self.decoder else {
+            self.position = target;
+            return Ok(target);
+        };
+        if let Some((chunk_position, chunk_bit_offset)) = decoder.seek_chunk(target) {
+            // the chunk offsets come from the archive, so one too large to add points past the entry
+            let bit_offset = self.data_bit_offset
+                .checked_add(chunk_bit_offset)
+                .ok_or_else(|| io::Error::from(ZipperError::MalformedTree))?;
+            self.reader.seek_from_start(bit_offset / 8)?;
+            self.reader.read_bits((bit_offset % 8) as u8)?;
+            self.position = chunk_position;
+        } else if target < self.position {
+            return Err(io::Error::new(ErrorKind::Unsupported, "Entry's compression method can only seek forward"));
+        }
 
-    // decompress each symbol in data segment, stopping at the end
-    let writer = &mut FileWriter::new(unarchived_filename);
-    let start_read_len = reader.read_len() as i64;
-    while !reader.eof() {
-        let read_len = reader.read_len() as i64;
-        if (read_len - start_read_len) >= block.data_bit_size as i64 {
-            break;
+        let mut skip_buf = [0u8; 4096];
+        while self.position < target {
+            let len = (target - self.position).min(skip_buf.len() as u64) as usize;
+            let len = decoder.decode(self.reader, &mut skip_buf[..len])?;
+            if len == 0 {
+                break;
+            }
+            self.position += len as u64;
         }
-        decompress_next_symbol(reader, writer, &root);
+        // positions past the end of the entry read nothing, like positions past the end of a file
+        self.position = target;
+        Ok(target)
     }
 }
 
-fn read_node(reader: &mut FileReader) -> Box<Node> {
-    let bit = reader.read_bit();
-    if bit == 1 {
-        Box::new(Node::leaf(reader.read_bits(8), 0))
-    } else {
-        let left = read_node(reader);
-        let right = read_node(reader);
-        Box::new(Node::internal(left, right, 0, 0))
+pub fn get_archive_header<R: Read>(reader: &mut BitReader<R>) -> Result<ArchiveHeader> {
+    let header = reader.read_archive_header()?;
+    // refuse versions with a different layout, and required features this version can't read
+    if header.version_major != VERSION_MAJOR {
+        return Err(ZipperError::UnsupportedVersion(header.version_major, header.version_minor));
+    }
+    let unsupported_flags = header.flags & REQUIRED_FLAGS_MASK & !SUPPORTED_FLAGS;
+    if unsupported_flags != 0 {
+        return Err(ZipperError::UnsupportedFlags(unsupported_flags));
     }
+    Ok(header)
 }
 
-fn decompress_next_symbol(reader: &mut FileReader, writer: &mut FileWriter, node: &Box<Node>) {
-    if node.is_leaf() {
-        writer.write_byte(node.plain_symbol);
+pub fn get_file_blocks<R: Read>(reader: &mut BitReader<R>) -> Result<Vec<FileBlock>> {
+    // read the block count, then each length prefixed block
+    let block_count = reader.read_u64().map_err(header_error)?;
+    let mut blocks = vec![];
+    for _ in 0..block_count {
+        blocks.push(reader.read_block()?);
+    }
+    Ok(blocks)
+}
+
+// the solid stream's code tables start the data segment, right after the blocks
+fn get_solid_tables<R: Read>(reader: &mut BitReader<R>, header: &ArchiveHeader) -> Result<Vec<DecodeTable>> {
+    if header.flags & FLAG_SOLID == 0 {
+        return Ok(vec![]);
+    }
+    let table_count = if header.flags & FLAG_CODE_BOOKS != 0 {
+        reader.read_u16().map_err(header_error)?
     } else {
-        let bit = reader.read_bit();
-        if bit == 0 {
-            let left = node.left.as_ref().expect("Expected left node to be Some");
-            decompress_next_symbol(reader, writer, left);
-        } else {
-            let right = node.right.as_ref().expect("Expected right node to be Some");
-            decompress_next_symbol(reader, writer, right);
+        1
+    };
+    let mut tables = vec![];
+    for _ in 0..table_count {
+        tables.push(read_code_lengths(reader, TABLE_SIZE)?);
+    }
+    Ok(tables)
+}
+
+// decompresses a stream created by compress_bytes back into the original buffer
+pub fn decompress_bytes(bytes: &[u8]) -> Result<Vec<u8>> {
+    let reader = &mut BitReader::new(bytes);
+    let byte_size = reader.read_u64().map_err(header_error)?;
+    let table = read_code_lengths(reader, TABLE_SIZE)?;
+
+    let mut output = vec![];
+    for _ in 0..byte_size {
+        output.push(table.decode_next_symbol(reader)? as u8);
+    }
+    Ok(output)
+}
+
+// decompresses a sequence of compress_bytes streams, such as the output of a streaming encoder
+pub struct HuffmanDecoder<R: Read> {
+    // the stream compressed blocks are read from
+    reader: BitReader<R>,
+    // the code table for the block being decoded
+    table: Option<DecodeTable>,
+    // the number of symbols left to decode in the current block
+    remaining: u64
+}
+
+impl<R: Read> HuffmanDecoder<R> {
+    pub fn new(inner: R) -> HuffmanDecoder<R> {
+        HuffmanDecoder {
+            reader: BitReader::new(inner),
+            table: None,
+            remaining: 0
+        }
+    }
+
+    pub fn get_ref(&self) -> &R {
+        self.reader.get_ref()
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        self.reader.get_mut()
+    }
+
+    // reads the header of the next block, returning false when the stream has ended
+    fn next_block(&mut self) -> Result<bool> {
+        // each block is padded to a byte and the stream ends on a block boundary
+        self.reader.align_to_byte();
+        if self.reader.eof()? {
+            return Ok(false);
+        }
+        self.remaining = self.reader.read_u64().map_err(header_error)?;
+        self.table = Some(read_code_lengths(&mut self.reader, TABLE_SIZE)?);
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for HuffmanDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.remaining == 0 {
+            if buf.is_empty() || !self.next_block()? {
+                return Ok(0);
+            }
+        }
+        let table = self.table.as_ref().expect("Code table is read with the block header");
+        let mut len = 0;
+        while len < buf.len() && self.remaining > 0 {
+            buf[len] = table.decode_next_symbol(&mut self.reader)? as u8;
+            self.remaining -= 1;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+fn decompress_files<R: Read + Seek>(archive: &mut ArchiveReader<R>, output_dir: &Path) -> Result<()> {
+    for index in 0..archive.blocks.len() {
+        decompress_file(archive, index, output_dir)?;
+    }
+    // restore directories last, deepest first, since extracting into them changes their mtime
+    // and their permissions might not allow writing the entries inside them
+    for block in archive.blocks.iter().rev().filter(|block| block.entry_type == EntryType::Directory) {
+        restore_dir_metadata(&output_dir.join(block.filename_rel_path()), block)?;
+    }
+    Ok(())
+}
+
+fn decompress_file<R: Read + Seek>(archive: &mut ArchiveReader<R>, index: usize, output_dir: &Path) -> Result<()> {
+    let block = &archive.blocks[index];
+    let filename_rel = block.filename_rel_path();
+    let unarchived_filename = &output_dir.join(&filename_rel);
+    // entries are never written through a symlink, whether the archive made it or it was already there
+    // directories are checked themselves too, since their metadata is restored through their path
+    match block.entry_type {
+        EntryType::Directory => check_no_symlinks(output_dir, &filename_rel)?,
+        _ => check_no_symlinks(output_dir, filename_rel.parent().unwrap_or(Path::new("")))?
+    }
+
+    if let Some(unarchived_parent) = unarchived_filename.parent() {
+        fs::create_dir_all(unarchived_parent)?;
+    }
+
+    match block.entry_type {
+        EntryType::Directory => {
+            fs::create_dir_all(unarchived_filename)?;
+        },
+        EntryType::Symlink => {
+            remove_existing(unarchived_filename)?;
+            create_symlink(&block.link_target_path(), unarchived_filename)?;
+        },
+        EntryType::HardLink => {
+            // the linked file is always extracted before any of its links
+            let target_rel = block.link_target_path();
+            check_no_symlinks(output_dir, target_rel.parent().unwrap_or(Path::new("")))?;
+            remove_existing(unarchived_filename)?;
+            fs::hard_link(output_dir.join(block.link_target_path()), unarchived_filename)?;
+        },
+        EntryType::File => {
+            // decompress each symbol in data segment straight into the unarchived file
+            remove_existing(unarchived_filename)?;
+            let mut entry = archive.by_index(index)?;
+            let mut file = File::create(unarchived_filename)?;
+            io::copy(&mut entry, &mut file)?;
+            restore_file_metadata(&file, entry.block())?;
+        }
+    }
+    Ok(())
+}
+
+// refuses to extract through a symlink at any part of the path, which would put the entry wherever the link points
+fn check_no_symlinks(output_dir: &Path, path_rel: &Path) -> Result<()> {
+    let mut path = output_dir.to_path_buf();
+    for component in path_rel.components() {
+        path.push(component);
+        match fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => {
+                return Err(ZipperError::UnsafePath(path_rel.to_string_lossy().into_owned()));
+            },
+            Ok(_) => {},
+            // nothing exists under a parent that doesn't exist yet
+            Err(_) => break
+        }
+    }
+    Ok(())
+}
+
+// removes a file or link left at the path by an earlier extraction, so it isn't written through
+fn remove_existing(path: &Path) -> io::Result<()> {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if !metadata.is_dir() => fs::remove_file(path),
+        _ => Ok(())
+    }
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, path: &Path) -> io::Result<()> {
+    std::os::unix::fs::symlink(target, path)
+}
+
+#[cfg(not(unix))]
+fn create_symlink(_target: &Path, _path: &Path) -> io::Result<()> {
+    Err(io::Error::new(ErrorKind::Unsupported, "Symlinks can only be extracted on unix"))
+}
+
+#[cfg(unix)]
+fn restore_dir_metadata(path: &Path, block: &FileBlock) -> io::Result<()> {
+    restore_file_metadata(&File::open(path)?, block)
+}
+
+// other platforms can't open a directory as a file to change its metadata
+#[cfg(not(unix))]
+fn restore_dir_metadata(_path: &Path, _block: &FileBlock) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn restore_file_metadata(file: &File, block: &FileBlock) -> io::Result<()> {
+    use std::os::unix::fs::{fchown, PermissionsExt};
+    // a zero mode means the archive didn't record permissions or owners
+    if block.mode != 0 {
+        // only privileged users can give files away, so failing to change owners isn't an error
+        match fchown(file, Some(block.uid), Some(block.gid)) {
+            Err(err) if err.kind() != ErrorKind::PermissionDenied => return Err(err),
+            _ => ()
+        }
+        // permissions are set after owners since changing owners can clear the setuid bits
+        file.set_permissions(fs::Permissions::from_mode(block.mode & 0o7777))?;
+    }
+    restore_file_mtime(file, block)
+}
+
+#[cfg(not(unix))]
+fn restore_file_metadata(file: &File, block: &FileBlock) -> io::Result<()> {
+    restore_file_mtime(file, block)
+}
+
+fn restore_file_mtime(file: &File, block: &FileBlock) -> io::Result<()> {
+    // a zero mtime means the archive didn't record one, and a time the platform can't represent is left unset
+    if block.mtime != 0 || block.mtime_nsec != 0 {
+        if let Some(modified) = mtime_to_system_time(block.mtime, block.mtime_nsec) {
+            file.set_modified(modified)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::time::{Duration, UNIX_EPOCH};
+    use crate::chunked::ChunkedCodec;
+    use crate::codec::{EntryEncoder, StoredCodec, METHOD_CHUNKED, METHOD_HUFFMAN, METHOD_RANS, METHOD_STORED};
+    use crate::compress::ArchiveWriter;
+    use crate::compress::tests::{archive_in_memory, noise, temp_dir};
+    use super::*;
+
+    #[test]
+    fn entries_are_read_in_any_order() {
+        let dir = temp_dir("random-access");
+        let files = [("a.txt", b"first file".repeat(20)), ("b.bin", noise(3000)), ("c.txt", b"third".to_vec())];
+        for (name, contents) in &files {
+            fs::write(dir.join(name), contents).unwrap();
         }
+        let mut archive = ArchiveReader::new(Cursor::new(archive_in_memory(&dir))).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let base = dir.file_name().unwrap().to_str().unwrap();
+        for i in [2, 0, 1, 2, 0] {
+            let (name, contents) = &files[i];
+            let mut decoded = vec![];
+            archive.by_name(format!("{}/{}", base, name)).unwrap().read_to_end(&mut decoded).unwrap();
+            assert_eq!(&decoded, contents);
+        }
+        let index = archive.entries().iter().position(|block| block.filename_rel.ends_with(b"b.bin")).unwrap();
+        let mut decoded = vec![];
+        archive.by_index(index).unwrap().read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, files[1].1);
+        assert!(matches!(archive.by_name("missing"), Err(ZipperError::EntryNotFound(_))));
+        assert!(matches!(archive.by_index(archive.entries().len()), Err(ZipperError::EntryNotFound(_))));
+    }
+
+    #[test]
+    fn offsets_that_overflow_are_rejected() {
+        let dir = temp_dir("overflow");
+        fs::write(dir.join("file"), b"contents").unwrap();
+        let mut archive = ArchiveReader::new(Cursor::new(archive_in_memory(&dir.join("file")))).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        archive.blocks[0].file_byte_offset = u64::MAX;
+        assert!(matches!(archive.by_index(0), Err(ZipperError::BadOffset(_))));
+        archive.blocks[0].file_byte_offset = 0;
+        archive.blocks[0].data_bit_size = u64::MAX;
+        assert!(matches!(archive.by_index(0), Err(ZipperError::BadOffset(_))));
+    }
+
+    // opens an in-memory archive whose header has been patched with a different version and flags
+    fn open_with_header(major: u16, flags: u32) -> Result<ArchiveReader<Cursor<Vec<u8>>>> {
+        let dir = temp_dir(&format!("version-{}-{:x}", major, flags));
+        fs::write(dir.join("file"), b"contents").unwrap();
+        let mut bytes = archive_in_memory(&dir.join("file"));
+        fs::remove_dir_all(&dir).unwrap();
+        // the version and flags follow the 8 byte signature
+        bytes[8..10].copy_from_slice(&major.to_le_bytes());
+        bytes[12..16].copy_from_slice(&flags.to_le_bytes());
+        ArchiveReader::new(Cursor::new(bytes))
+    }
+
+    #[test]
+    fn unknown_versions_and_required_flags_are_refused() {
+        assert!(open_with_header(VERSION_MAJOR, 0).is_ok());
+        assert!(matches!(open_with_header(VERSION_MAJOR + 1, 0), Err(ZipperError::UnsupportedVersion(major, _)) if major == VERSION_MAJOR + 1));
+        assert!(matches!(open_with_header(VERSION_MAJOR, 0x0000_8000), Err(ZipperError::UnsupportedFlags(0x0000_8000))));
+    }
+
+    #[test]
+    fn unknown_optional_flags_are_ignored() {
+        let mut archive = open_with_header(VERSION_MAJOR, 0x8000_0000).unwrap();
+        assert_eq!(archive.header.flags, 0x8000_0000);
+        let mut decoded = vec![];
+        archive.by_index(0).unwrap().read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"contents");
+    }
+
+    #[test]
+    fn corrupted_entries_fail_their_checksum() {
+        let dir = temp_dir("corrupted");
+        fs::write(dir.join("file"), b"some text to be corrupted ".repeat(40)).unwrap();
+        let mut bytes = archive_in_memory(&dir.join("file"));
+        fs::remove_dir_all(&dir).unwrap();
+        // the last bytes of the archive are the entry's compressed data
+        let len = bytes.len();
+        bytes[len - 2] ^= 0xFF;
+
+        let mut archive = ArchiveReader::new(Cursor::new(bytes)).unwrap();
+        let mut decoded = vec![];
+        let err = archive.by_index(0).unwrap().read_to_end(&mut decoded).unwrap_err();
+        assert!(matches!(ZipperError::from(err), ZipperError::ChecksumMismatch(_)));
+        assert!(matches!(archive.test_entry(0), Err(ZipperError::ChecksumMismatch(_))));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn names_that_arent_unicode_round_trip() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let dir = temp_dir("non-unicode");
+        let name = OsStr::from_bytes(b"caf\xE9.txt");
+        fs::write(dir.join(name), b"latin-1 name").unwrap();
+        let mut archive = ArchiveReader::new(Cursor::new(archive_in_memory(&dir))).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let path = Path::new(dir.file_name().unwrap()).join(name);
+        assert!(archive.entries().iter().any(|block| block.filename_rel_path() == path));
+        let mut decoded = vec![];
+        archive.by_name(&path).unwrap().read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"latin-1 name");
+
+        let output = temp_dir("non-unicode-output");
+        archive.extract_all(&output).unwrap();
+        assert_eq!(fs::read(output.join(&path)).unwrap(), b"latin-1 name");
+        fs::remove_dir_all(&output).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extraction_restores_mode_and_mtime() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+
+        let dir = temp_dir("metadata");
+        let path = dir.join("script.sh");
+        fs::write(&path, b"#!/bin/sh\n").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o750)).unwrap();
+        File::options().write(true).open(&path).unwrap().set_modified(UNIX_EPOCH + Duration::new(1_000_000_000, 123)).unwrap();
+        let mut archive = ArchiveReader::new(Cursor::new(archive_in_memory(&path))).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let output = temp_dir("metadata-output");
+        archive.extract_all(&output).unwrap();
+        let metadata = fs::metadata(output.join("script.sh")).unwrap();
+        assert_eq!(metadata.mode() & 0o7777, 0o750);
+        assert_eq!((metadata.mtime(), metadata.mtime_nsec()), (1_000_000_000, 123));
+
+        // a time the platform can't represent is left unset instead of failing the extraction
+        archive.blocks[0].mtime = i64::MAX;
+        archive.extract_all(&output).unwrap();
+        fs::remove_dir_all(&output).unwrap();
     }
-}
\ No newline at end of file
+
+    #[cfg(unix)]
+    #[test]
+    fn links_are_restored_as_links() {
+        use std::os::unix::fs::{symlink, MetadataExt};
+
+        let dir = temp_dir("links");
+        fs::write(dir.join("file.txt"), b"linked contents").unwrap();
+        symlink("file.txt", dir.join("symlink")).unwrap();
+        fs::hard_link(dir.join("file.txt"), dir.join("hard_link")).unwrap();
+        fs::create_dir(dir.join("empty")).unwrap();
+        let mut archive = ArchiveReader::new(Cursor::new(archive_in_memory(&dir))).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let output = temp_dir("links-output");
+        archive.extract_all(&output).unwrap();
+        let extracted = output.join(dir.file_name().unwrap());
+        assert_eq!(fs::read_link(extracted.join("symlink")).unwrap(), Path::new("file.txt"));
+        let file = fs::metadata(extracted.join("file.txt")).unwrap();
+        let hard_link = fs::symlink_metadata(extracted.join("hard_link")).unwrap();
+        assert_eq!((hard_link.dev(), hard_link.ino()), (file.dev(), file.ino()));
+        assert_eq!(fs::read(extracted.join("hard_link")).unwrap(), b"linked contents");
+        assert!(extracted.join("empty").is_dir());
+        fs::remove_dir_all(&output).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn extraction_refuses_to_write_through_a_symlink() {
+        use std::os::unix::fs::symlink;
+
+        let dir = temp_dir("symlink-escape");
+        fs::write(dir.join("file.txt"), b"contents").unwrap();
+        let mut archive = ArchiveReader::new(Cursor::new(archive_in_memory(&dir))).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // a symlink already in the output directory where the archived directory goes
+        let output = temp_dir("symlink-escape-output");
+        let elsewhere = temp_dir("symlink-escape-elsewhere");
+        symlink(&elsewhere, output.join(dir.file_name().unwrap())).unwrap();
+        assert!(matches!(archive.extract_all(&output), Err(ZipperError::UnsafePath(_))));
+        assert_eq!(fs::read_dir(&elsewhere).unwrap().count(), 0);
+        fs::remove_dir_all(&output).unwrap();
+        fs::remove_dir_all(&elsewhere).unwrap();
+    }
+
+    #[test]
+    fn incompressible_files_are_stored() {
+        let dir = temp_dir("stored");
+        let files = [("noise.bin", noise(4096)), ("text.txt", b"mostly the same few words ".repeat(100))];
+        for (name, contents) in &files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        let mut archive = ArchiveReader::new(Cursor::new(archive_in_memory(&dir))).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        for (name, contents) in &files {
+            let index = archive.entries().iter().position(|block| block.filename_rel.ends_with(name.as_bytes())).unwrap();
+            let block = &archive.entries()[index];
+            // random bytes have nothing for huffman coding to exploit, so they are copied with no code table
+            if name.ends_with(".bin") {
+                assert_eq!((block.method, block.tree_bit_size, block.data_bit_size), (METHOD_STORED, 0, 8 * contents.len() as u64));
+            } else {
+                assert_eq!(block.method, METHOD_HUFFMAN);
+            }
+            let mut decoded = vec![];
+            archive.by_index(index).unwrap().read_to_end(&mut decoded).unwrap();
+            assert_eq!(&decoded, contents);
+        }
+    }
+
+    // the stored method under a method id that isn't built in
+    struct CustomCodec;
+
+    impl Codec for CustomCodec {
+        fn method(&self) -> u8 {
+            200
+        }
+
+        fn name(&self) -> &'static str {
+            "custom"
+        }
+
+        fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>> {
+            StoredCodec.create_encoder(input)
+        }
+
+        fn create_decoder(&self, reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>> {
+            StoredCodec.create_decoder(reader, block)
+        }
+    }
+
+    #[test]
+    fn registered_codecs_decode_their_entries() {
+        let dir = temp_dir("custom");
+        fs::write(dir.join("file.txt"), b"some contents").unwrap();
+        let mut archive = vec![];
+        let mut writer = ArchiveWriter::new(&mut archive);
+        assert!(matches!(writer.set_method(200), Err(ZipperError::UnsupportedMethod(200))));
+        writer.register_codec(Box::new(CustomCodec));
+        writer.set_method(200).unwrap();
+        writer.add_entry(&dir).unwrap();
+        writer.finish().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // a reader without the codec can list the entry but not decode it
+        let mut reader = ArchiveReader::new(Cursor::new(&archive)).unwrap();
+        let index = reader.entries().iter().position(|block| block.filename_rel.ends_with(b"file.txt")).unwrap();
+        assert_eq!(reader.entries()[index].method, 200);
+        assert!(matches!(reader.by_index(index), Err(ZipperError::UnsupportedMethod(200))));
+
+        reader.register_codec(Box::new(CustomCodec));
+        let mut decoded = vec![];
+        reader.by_index(index).unwrap().read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, b"some contents");
+    }
+
+    #[test]
+    fn entries_seek_by_chunk_or_forward() {
+        let dir = temp_dir("seek");
+        let contents: Vec<u8> = (0..5000u32).flat_map(|i| format!("line {} ", i % 97).into_bytes()).collect();
+        fs::write(dir.join("text.txt"), &contents).unwrap();
+        let archive_with = |method: u8| {
+            let mut archive = vec![];
+            let mut writer = ArchiveWriter::new(&mut archive);
+            writer.register_codec(Box::new(ChunkedCodec::new(1000, 15)));
+            writer.set_method(method).unwrap();
+            writer.add_entry(&dir).unwrap();
+            writer.finish().unwrap();
+            ArchiveReader::new(Cursor::new(archive)).unwrap()
+        };
+        let mut chunked = archive_with(METHOD_CHUNKED);
+        let mut huffman = archive_with(METHOD_HUFFMAN);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let read_at = |entry: &mut dyn Read, len: usize| {
+            let mut buf = vec![0; len];
+            entry.read_exact(&mut buf).unwrap();
+            buf
+        };
+        let index = chunked.entries().iter().position(|block| block.filename_rel.ends_with(b"text.txt")).unwrap();
+        assert_eq!(chunked.entries()[index].method, METHOD_CHUNKED);
+        let mut entry = chunked.by_index(index).unwrap();
+        assert_eq!(entry.seek(SeekFrom::Start(2500)).unwrap(), 2500);
+        assert_eq!(read_at(&mut entry, 10), &contents[2500..2510]);
+        // chunks decode on their own, so seeking back jumps to an earlier chunk
+        assert_eq!(entry.seek(SeekFrom::Current(-2000)).unwrap(), 510);
+        assert_eq!(read_at(&mut entry, 10), &contents[510..520]);
+        entry.seek(SeekFrom::End(-5)).unwrap();
+        assert_eq!(read_at(&mut entry, 5), &contents[contents.len() - 5..]);
+
+        let mut entry = huffman.by_index(index).unwrap();
+        assert_eq!(entry.seek(SeekFrom::Current(100)).unwrap(), 100);
+        assert_eq!(read_at(&mut entry, 10), &contents[100..110]);
+        assert_eq!(entry.seek(SeekFrom::Start(50)).unwrap_err().kind(), ErrorKind::Unsupported);
+        assert_eq!(entry.seek(SeekFrom::Current(-1000)).unwrap_err().kind(), ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn entries_keep_the_method_they_were_added_with() {
+        let dir = temp_dir("methods");
+        // the contents differ, or the second file would only point at the first one's data
+        let contents = [b"mostly the same few words ".repeat(100), b"mostly the same few other words ".repeat(100)];
+        fs::write(dir.join("rans.txt"), &contents[0]).unwrap();
+        fs::write(dir.join("huffman.txt"), &contents[1]).unwrap();
+        let mut archive = vec![];
+        let mut writer = ArchiveWriter::new(&mut archive);
+        assert!(matches!(writer.add_entry_with_method(dir.join("rans.txt"), 200), Err(ZipperError::UnsupportedMethod(200))));
+        writer.add_entry_with_method(dir.join("rans.txt"), METHOD_RANS).unwrap();
+        writer.add_entry(dir.join("huffman.txt")).unwrap();
+        writer.finish().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // only entries whose codec stores it have a huffman size to compare against
+        let mut reader = ArchiveReader::new(Cursor::new(archive)).unwrap();
+        let methods: Vec<u8> = reader.entries().iter().map(|block| block.method).collect();
+        assert_eq!(methods, [METHOD_RANS, METHOD_HUFFMAN]);
+        assert!(reader.huffman_bit_size(0).unwrap().is_some_and(|bit_size| bit_size > 0));
+        assert_eq!(reader.huffman_bit_size(1).unwrap(), None);
+        for (index, contents) in contents.iter().enumerate() {
+            let mut decoded = vec![];
+            reader.by_index(index).unwrap().read_to_end(&mut decoded).unwrap();
+            assert_eq!(&decoded, contents);
+        }
+    }
+
+    #[test]
+    fn solid_entries_share_one_code_table() {
+        let dir = temp_dir("solid");
+        let texts: Vec<Vec<u8>> = (0..3).map(|i| format!("the same kind of text, part {} ", i).repeat(40).into_bytes()).collect();
+        for (i, text) in texts.iter().enumerate() {
+            fs::write(dir.join(format!("{}.txt", i)), text).unwrap();
+        }
+        let binary = noise(2000);
+        fs::write(dir.join("noise.bin"), &binary).unwrap();
+        let mut archive = vec![];
+        let mut writer = ArchiveWriter::new(&mut archive);
+        writer.set_solid(true);
+        writer.add_entry(&dir).unwrap();
+        writer.finish().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let mut reader = ArchiveReader::new(Cursor::new(&archive)).unwrap();
+        assert_ne!(reader.header().flags & FLAG_SOLID, 0);
+        let position = |reader: &ArchiveReader<_>, name: &[u8]| {
+            reader.entries().iter().position(|block: &FileBlock| block.filename_rel.ends_with(name)).unwrap()
+        };
+        // the text files follow each other in the stream, so at least one of them starts mid byte
+        let solid: Vec<&FileBlock> = reader.entries().iter().filter(|block| block.method == METHOD_SOLID).collect();
+        assert_eq!(solid.len(), texts.len());
+        assert!(solid.iter().all(|block| block.tree_bit_size == 0));
+        assert!(solid.iter().any(|block| block.bit_offset != 0));
+        // the noise would grow with codes counted for text, so it's compressed on its own
+        let noise_index = position(&reader, b"noise.bin");
+        assert_ne!(reader.entries()[noise_index].method, METHOD_SOLID);
+
+        for (i, text) in texts.iter().enumerate() {
+            let index = position(&reader, format!("{}.txt", i).as_bytes());
+            let mut decoded = vec![];
+            reader.by_index(index).unwrap().read_to_end(&mut decoded).unwrap();
+            assert_eq!(&decoded, text);
+        }
+        let mut decoded = vec![];
+        reader.by_index(noise_index).unwrap().read_to_end(&mut decoded).unwrap();
+        assert_eq!(decoded, binary);
+    }
+
+    #[test]
+    fn solid_entries_are_clustered_into_code_books() {
+        let dir = temp_dir("code-books");
+        let words = b"some lowercase words for the first kind of file ".repeat(30);
+        let digits = b"0123 4567 89 ".repeat(120);
+        let files = [("words1.txt", &words), ("digits1.txt", &digits), ("words2.txt", &words), ("digits2.txt", &digits)];
+        for (name, contents) in files {
+            fs::write(dir.join(name), contents).unwrap();
+        }
+        let mut archive = vec![];
+        let mut writer = ArchiveWriter::new(&mut archive);
+        writer.set_code_book_count(2);
+        writer.add_entry(&dir).unwrap();
+        writer.finish().unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        // files with alike bytes share a code table, and each kind gets its own
+        let mut reader = ArchiveReader::new(Cursor::new(&archive)).unwrap();
+        assert_eq!(reader.header().flags & (FLAG_SOLID | FLAG_CODE_BOOKS), FLAG_SOLID | FLAG_CODE_BOOKS);
+        let code_book = |reader: &ArchiveReader<_>, name: &[u8]| {
+            let index = reader.entries().iter().position(|block: &FileBlock| block.filename_rel.ends_with(name)).unwrap();
+            assert_eq!(reader.entries()[index].method, METHOD_SOLID);
+            (index, reader.entries()[index].code_book)
+        };
+        assert_eq!(code_book(&reader, b"words1.txt").1, code_book(&reader, b"words2.txt").1);
+        assert_eq!(code_book(&reader, b"digits1.txt").1, code_book(&reader, b"digits2.txt").1);
+        assert_ne!(code_book(&reader, b"words1.txt").1, code_book(&reader, b"digits1.txt").1);
+
+        for (name, contents) in files {
+            let (index, _) = code_book(&reader, name.as_bytes());
+            let mut decoded = vec![];
+            reader.by_index(index).unwrap().read_to_end(&mut decoded).unwrap();
+            assert_eq!(&decoded, contents);
+        }
+    }
+
+    #[test]
+    fn identical_files_share_their_compressed_data() {
+        let dir = temp_dir("dedup");
+        let contents = b"a file copied around the repository ".repeat(50);
+        let mut changed = contents.clone();
+        changed[0] = b'A';
+        fs::create_dir_all(dir.join("copy")).unwrap();
+        fs::write(dir.join("original.txt"), &contents).unwrap();
+        fs::write(dir.join("copy/original.txt"), &contents).unwrap();
+        // the same size but not the same contents
+        fs::write(dir.join("changed.txt"), &changed).unwrap();
+        for solid in [false, true] {
+            let mut archive = vec![];
+            let mut writer = ArchiveWriter::new(&mut archive);
+            writer.set_solid(solid);
+            writer.add_entry(&dir).unwrap();
+            writer.finish().unwrap();
+
+            let mut reader = ArchiveReader::new(Cursor::new(&archive)).unwrap();
+            let duplicates: Vec<usize> = (0..reader.entries().len()).filter(|&i| reader.entries()[i].is_duplicate()).collect();
+            assert_eq!(duplicates.len(), 1, "solid: {}", solid);
+            let duplicate = &reader.entries()[duplicates[0]];
+            let original = reader.entries().iter().find(|block| block.filename_rel == duplicate.link_target).unwrap();
+            assert_eq!(
+                (duplicate.method, duplicate.file_byte_offset, duplicate.bit_offset, duplicate.data_bit_size),
+                (original.method, original.file_byte_offset, original.bit_offset, original.data_bit_size)
+            );
+
+            for index in 0..reader.entries().len() {
+                let block = &reader.entries()[index];
+                if block.entry_type != EntryType::File {
+                    continue;
+                }
+                let expected = if block.filename_rel.ends_with(b"changed.txt") { &changed } else { &contents };
+                let mut decoded = vec![];
+                reader.by_index(index).unwrap().read_to_end(&mut decoded).unwrap();
+                assert_eq!(&decoded, expected);
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
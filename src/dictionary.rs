@@ -0,0 +1,336 @@
+// Joseph Prichard
+// 10/18/2026
+// Trained code books kept outside of archives, so entries coded with one only store the id of the dictionary
+
+use std::{fs, io};
+use std::fs::File;
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+use std::rc::Rc;
+use crate::bitwise::SymbolCode;
+use crate::block::FileBlock;
+use crate::canonical::{create_canonical_table, is_complete_code, DecodeTable};
+use crate::charset::DICT_SIG;
+use crate::checksum::Crc32;
+use crate::codec::{Codec, EntryDecoder, EntryEncoder, METHOD_DICTIONARY};
+use crate::error::{Result, ZipperError};
+use crate::huffman;
+use crate::huffman::tree_error;
+use crate::read::BitReader;
+use crate::write::BitWriter;
+
+// bytes the dictionary has no code for are written as this symbol followed by the byte itself
+const ESCAPE: usize = 256;
+const SYMBOL_COUNT: usize = 257;
+
+// a code book trained on sample files, identified by a checksum of its code lengths
+pub struct Dictionary {
+    id: u32,
+    code_lens: Vec<u8>,
+    symbol_table: Vec<SymbolCode>,
+    decode_table: DecodeTable
+}
+
+impl Dictionary {
+    // builds a code book from the bytes of every file under the paths, with an escape for bytes the samples lack
+    pub fn train<P: AsRef<Path>>(paths: &[P], max_code_len: u8) -> Result<Dictionary> {
+        let mut freq_table = vec![0u64; SYMBOL_COUNT];
+        for path in paths {
+            count_path(path.as_ref(), &mut freq_table)?;
+        }
+        Ok(Dictionary::from_freq_table(freq_table, max_code_len))
+    }
+
+    fn from_freq_table(mut freq_table: Vec<u64>, max_code_len: u8) -> Dictionary {
+        // the escape always gets a code, but it is rare enough that it doesn't take a short one from a sampled byte
+        freq_table[ESCAPE] = 1;
+        let tree = huffman::create_code_tree(&freq_table, max_code_len);
+        let code_lens = huffman::create_code_table(&tree, SYMBOL_COUNT)
+            .iter()
+            .map(|symbol_code| symbol_code.bit_len)
+            .collect();
+        Dictionary::from_code_lens(code_lens)
+    }
+
+    // the id is the checksum of the code lengths, so training on the same samples gives the same id
+    fn from_code_lens(code_lens: Vec<u8>) -> Dictionary {
+        let mut crc = Crc32::new();
+        crc.update(&code_lens);
+        Dictionary {
+            id: crc.checksum(),
+            symbol_table: create_canonical_table(&code_lens),
+            decode_table: DecodeTable::new(&code_lens),
+            code_lens
+        }
+    }
+
+    pub fn open<P: AsRef<Path>>(dictionary_filepath: P) -> Result<Dictionary> {
+        Dictionary::read_from(File::open(dictionary_filepath)?)
+    }
+
+    // reads a dictionary file: the signature, the id, then the length of each symbol's code as a byte
+    pub fn read_from<R: Read>(inner: R) -> Result<Dictionary> {
+        let mut reader = BitReader::new(inner);
+        let sig = reader.read_u64().map_err(dictionary_error)?;
+        if sig != DICT_SIG {
+            return Err(ZipperError::BadDictionary);
+        }
+        let id = reader.read_u32().map_err(dictionary_error)?;
+        let code_lens = reader.read_bytes(SYMBOL_COUNT).map_err(dictionary_error)?;
+        if !is_complete_code(&code_lens) || code_lens[ESCAPE] == 0 {
+            return Err(ZipperError::BadDictionary);
+        }
+        let dictionary = Dictionary::from_code_lens(code_lens);
+        if dictionary.id != id {
+            return Err(ZipperError::BadDictionary);
+        }
+        Ok(dictionary)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, dictionary_filepath: P) -> Result<()> {
+        self.write_to(File::create(dictionary_filepath)?)
+    }
+
+    pub fn write_to<W: Write>(&self, inner: W) -> Result<()> {
+        let mut writer = BitWriter::new(inner);
+        writer.write_u64(DICT_SIG)?;
+        writer.write_u32(self.id)?;
+        writer.write_bytes(&self.code_lens)?;
+        writer.flush()?;
+        Ok(())
+    }
+
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+
+    // the bits the byte takes, including the escape and the byte itself when the dictionary has no code for it
+    fn byte_bit_len(&self, byte: usize) -> u64 {
+        match self.symbol_table[byte].bit_len {
+            0 => self.symbol_table[ESCAPE].bit_len as u64 + 8,
+            bit_len => bit_len as u64
+        }
+    }
+}
+
+fn count_path(path: &Path, freq_table: &mut [u64]) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(path)?;
+    if metadata.is_dir() {
+        for entry in fs::read_dir(path)? {
+            count_path(&entry?.path(), freq_table)?;
+        }
+    } else if metadata.is_file() {
+        let file_freq_table = huffman::create_freq_table(File::open(path)?)?;
+        for (freq, file_freq) in freq_table.iter_mut().zip(file_freq_table) {
+            *freq += file_freq;
+        }
+    }
+    Ok(())
+}
+
+// a dictionary file that ends early is malformed rather than an i/o failure
+fn dictionary_error(err: io::Error) -> ZipperError {
+    if err.kind() == ErrorKind::UnexpectedEof {
+        ZipperError::BadDictionary
+    } else {
+        ZipperError::Io(err)
+    }
+}
+
+// codes files with a trained dictionary, without one it can only report the id an entry needs
+#[derive(Default)]
+pub struct DictionaryCodec {
+    dictionary: Option<Rc<Dictionary>>
+}
+
+impl DictionaryCodec {
+    pub fn new(dictionary: Dictionary) -> DictionaryCodec {
+        DictionaryCodec { dictionary: Some(Rc::new(dictionary)) }
+    }
+}
+
+impl Codec for DictionaryCodec {
+    fn method(&self) -> u8 {
+        METHOD_DICTIONARY
+    }
+
+    fn name(&self) -> &'static str {
+        "dictionary"
+    }
+
+    fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>> {
+        let dictionary = self.dictionary
+            .clone()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "The dictionary method needs a dictionary"))?;
+        let freq_table = huffman::create_freq_table(input)?;
+        let mut data_bit_size = 0;
+        for (byte, freq) in freq_table.iter().enumerate() {
+            data_bit_size += freq * dictionary.byte_bit_len(byte);
+        }
+        Ok(Box::new(DictionaryEncoder {
+            dictionary,
+            data_bit_size,
+            byte_size: freq_table.iter().sum()
+        }))
+    }
+
+    fn create_decoder(&self, reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>> {
+        let id = reader.read_u32().map_err(tree_error)?;
+        match &self.dictionary {
+            Some(dictionary) if dictionary.id == id => Ok(Box::new(DictionaryDecoder {
+                dictionary: dictionary.clone(),
+                remaining: block.original_byte_size
+            })),
+            _ => Err(ZipperError::MissingDictionary(id))
+        }
+    }
+}
+
+struct DictionaryEncoder {
+    dictionary: Rc<Dictionary>,
+    // the bit size of the file once compressed
+    data_bit_size: u64,
+    // the byte size of the file the sizes were counted for
+    byte_size: u64
+}
+
+impl EntryEncoder for DictionaryEncoder {
+    // only the id of the dictionary is stored, the code book itself stays outside the archive
+    fn header_bit_size(&self) -> u64 {
+        32
+    }
+
+    fn data_bit_size(&self) -> Option<u64> {
+        Some(self.data_bit_size)
+    }
+
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()> {
+        writer.write_u32(self.dictionary.id)?;
+        // stop at the size the file was counted at in case it has grown since
+        let mut reader = BitReader::new(input.take(self.byte_size));
+        let symbol_table = &self.dictionary.symbol_table;
+        while !reader.eof()? {
+            let byte = reader.read_byte()?;
+            let symbol_code = &symbol_table[byte as usize];
+            if symbol_code.bit_len > 0 {
+                writer.write_symbol(symbol_code)?;
+            } else {
+                writer.write_symbol(&symbol_table[ESCAPE])?;
+                writer.write_bits(byte, 8)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+struct DictionaryDecoder {
+    dictionary: Rc<Dictionary>,
+    // the number of bytes left to decode
+    remaining: u64
+}
+
+impl EntryDecoder for DictionaryDecoder {
+    fn decode(&mut self, reader: &mut BitReader<dyn Read + '_>, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() && self.remaining > 0 {
+            let symbol = self.dictionary.decode_table.decode_next_symbol(reader)?;
+            buf[len] = if symbol as usize == ESCAPE {
+                reader.read_bits(8)?
+            } else {
+                symbol as u8
+            };
+            self.remaining -= 1;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use super::*;
+
+    // trained on lowercase text, so capitals and punctuation have to be escaped
+    fn sample_dictionary() -> Dictionary {
+        let mut freq_table = huffman::create_freq_table(&b"the quick brown fox jumps over the lazy dog"[..]).unwrap();
+        freq_table.push(0);
+        Dictionary::from_freq_table(freq_table, 15)
+    }
+
+    // encodes then decodes the input, returning the decoded bytes and the bit size of the encoded data
+    fn round_trip(codec: &DictionaryCodec, input: &[u8]) -> (Vec<u8>, u64) {
+        let encoder = codec.create_encoder(&mut &input[..]).unwrap();
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        encoder.encode(&mut &input[..], &mut writer).unwrap();
+        let data_bit_size = writer.write_len() - encoder.header_bit_size();
+        assert_eq!(encoder.data_bit_size(), Some(data_bit_size));
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut block = FileBlock::new(b"entry", Path::new(""));
+        block.original_byte_size = input.len() as u64;
+        let mut reader = BitReader::new(bytes.as_slice());
+        let mut decoder = codec.create_decoder(&mut reader, &block).ok().unwrap();
+        let mut decoded = vec![0; input.len()];
+        let mut len = 0;
+        while len < decoded.len() {
+            len += decoder.decode(&mut reader, &mut decoded[len..]).unwrap();
+        }
+        (decoded, data_bit_size)
+    }
+
+    #[test]
+    fn bytes_outside_the_samples_are_escaped() {
+        let dictionary = sample_dictionary();
+        assert!(dictionary.code_lens[ESCAPE] > 0);
+        assert_eq!(dictionary.code_lens[b'T' as usize], 0);
+        assert_eq!(dictionary.byte_bit_len(b'T' as usize), dictionary.code_lens[ESCAPE] as u64 + 8);
+
+        let input = b"The Lazy Dog, jumped over by the quick brown fox!";
+        let expected_bit_size: u64 = input.iter().map(|&byte| dictionary.byte_bit_len(byte as usize)).sum();
+        let (decoded, data_bit_size) = round_trip(&DictionaryCodec::new(dictionary), input);
+        assert_eq!(decoded, input);
+        assert_eq!(data_bit_size, expected_bit_size);
+    }
+
+    #[test]
+    fn dictionary_files_round_trip() {
+        let dictionary = sample_dictionary();
+        let mut bytes = vec![];
+        dictionary.write_to(&mut bytes).unwrap();
+        let read_back = Dictionary::read_from(Cursor::new(&bytes)).unwrap();
+        assert_eq!(read_back.id(), dictionary.id());
+        assert_eq!(read_back.code_lens, dictionary.code_lens);
+
+        // a changed code length no longer matches the id
+        *bytes.last_mut().unwrap() ^= 1;
+        assert!(matches!(Dictionary::read_from(Cursor::new(&bytes)), Err(ZipperError::BadDictionary)));
+        assert!(matches!(Dictionary::read_from(Cursor::new(&bytes[..20])), Err(ZipperError::BadDictionary)));
+        assert!(matches!(Dictionary::read_from(Cursor::new(b"zipper archive")), Err(ZipperError::BadDictionary)));
+    }
+
+    #[test]
+    fn entries_need_the_dictionary_they_were_coded_with() {
+        let dictionary = sample_dictionary();
+        let id = dictionary.id();
+        let mut encoded = vec![];
+        let mut writer = BitWriter::new(&mut encoded);
+        writer.write_u32(id).unwrap();
+        drop(writer);
+        let block = FileBlock::new(b"entry", Path::new(""));
+        let result = DictionaryCodec::default().create_decoder(&mut BitReader::new(Cursor::new(&encoded)), &block);
+        assert!(matches!(result, Err(ZipperError::MissingDictionary(missing)) if missing == id));
+
+        // a dictionary trained on other samples has another id
+        let mut freq_table = huffman::create_freq_table(&b"0123456789"[..]).unwrap();
+        freq_table.push(0);
+        let other = DictionaryCodec::new(Dictionary::from_freq_table(freq_table, 15));
+        let result = other.create_decoder(&mut BitReader::new(Cursor::new(&encoded)), &block);
+        assert!(matches!(result, Err(ZipperError::MissingDictionary(missing)) if missing == id));
+
+        // and without a dictionary nothing can be encoded
+        assert!(DictionaryCodec::default().create_encoder(&mut &b"text"[..]).is_err());
+    }
+}
@@ -0,0 +1,90 @@
+// Joseph Prichard
+// 10/18/2026
+// Error type for failures while reading or writing archives
+
+use std::{error, fmt, io};
+
+#[derive(Debug)]
+pub enum ZipperError {
+    // an underlying read or write on a file failed
+    Io(io::Error),
+    // the archive doesn't start with the zipr signature
+    BadSignature,
+    // the archive was written with a major version this reader can't parse
+    UnsupportedVersion(u16, u16),
+    // the archive uses required features this reader doesn't know
+    UnsupportedFlags(u32),
+    // the archive ends or breaks off before the header segment is complete
+    TruncatedHeader,
+    // the encoded tree for a block can't be decoded into a valid tree
+    MalformedTree,
+    // a block points to compressed data past the end of the archive
+    BadOffset(u64),
+    // no entry in the archive has the requested name or index
+    EntryNotFound(String),
+    // the decompressed contents of an entry don't match the checksum stored for it
+    ChecksumMismatch(String),
+    // an entry's path is empty, absolute, or climbs out of the directory it is extracted into
+    UnsafePath(String),
+    // a block has an entry type this reader doesn't know
+    UnknownEntryType(u8),
+    // a block is stored with a compression method this reader doesn't know
+    UnsupportedMethod(u8),
+    // a block is coded with a trained dictionary that wasn't given, or a different one was
+    MissingDictionary(u32),
+    // a dictionary file doesn't start with the dictionary signature or its code book is malformed
+    BadDictionary
+}
+
+pub type Result<T> = std::result::Result<T, ZipperError>;
+
+impl fmt::Display for ZipperError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZipperError::Io(err) => write!(f, "I/O error: {}", err),
+            ZipperError::BadSignature => write!(f, "File is not a zipr archive"),
+            ZipperError::UnsupportedVersion(major, minor) => write!(f, "Archive format version {}.{} is not supported", major, minor),
+            ZipperError::UnsupportedFlags(flags) => write!(f, "Archive uses unsupported features {:#010x}", flags),
+            ZipperError::TruncatedHeader => write!(f, "Archive header is truncated"),
+            ZipperError::MalformedTree => write!(f, "Archive contains a malformed code tree"),
+            ZipperError::BadOffset(offset) => write!(f, "Archive block points past the end of the archive at offset {}", offset),
+            ZipperError::EntryNotFound(name) => write!(f, "Archive has no entry named {}", name),
+            ZipperError::ChecksumMismatch(name) => write!(f, "Checksum mismatch for {}, the archive is corrupted", name),
+            ZipperError::UnsafePath(path) => write!(f, "Archive entry {} points outside of the extraction directory", path),
+            ZipperError::UnknownEntryType(entry_type) => write!(f, "Archive contains an entry of unknown type {}", entry_type),
+            ZipperError::UnsupportedMethod(method) => write!(f, "Compression method {} is not supported", method),
+            ZipperError::MissingDictionary(id) => write!(f, "Archive entry needs dictionary {:08x}, which wasn't given", id),
+            ZipperError::BadDictionary => write!(f, "File is not a valid zipper dictionary")
+        }
+    }
+}
+
+impl error::Error for ZipperError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ZipperError::Io(err) => Some(err),
+            _ => None
+        }
+    }
+}
+
+// lets errors pass through the io traits, keeping the underlying error when there is one
+impl From<ZipperError> for io::Error {
+    fn from(err: ZipperError) -> io::Error {
+        match err {
+            ZipperError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err)
+        }
+    }
+}
+
+impl From<io::Error> for ZipperError {
+    fn from(err: io::Error) -> ZipperError {
+        // unwrap errors that passed through the io traits back into their original kind
+        if err.get_ref().is_some_and(|inner| inner.is::<ZipperError>()) {
+            let inner = err.into_inner().expect("Checked the error has an inner error");
+            return *inner.downcast::<ZipperError>().expect("Checked the inner error is a ZipperError");
+        }
+        ZipperError::Io(err)
+    }
+}
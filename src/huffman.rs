@@ -0,0 +1,432 @@
+// Joseph Prichard
+// 10/18/2026
+// Huffman coding codec, encoding each byte with a canonical code built from the byte frequencies
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use std::iter::Peekable;
+use std::rc::Rc;
+use crate::bitwise;
+use crate::bitwise::SymbolCode;
+use crate::block::FileBlock;
+use crate::canonical;
+use crate::canonical::{is_complete_code, DecodeTable, MAX_CODE_LEN};
+use crate::codec::{Codec, EntryDecoder, EntryEncoder, METHOD_HUFFMAN};
+use crate::error::{Result, ZipperError};
+use crate::read::BitReader;
+use crate::tree::Tree;
+use crate::write::BitWriter;
+
+pub(crate) const TABLE_SIZE: usize = 256;
+// the longest code a symbol is given unless a different limit is set
+pub const DEFAULT_MAX_CODE_LEN: u8 = 15;
+
+pub struct HuffmanCodec {
+    // the longest code any symbol is given
+    max_code_len: u8
+}
+
+impl HuffmanCodec {
+    // limits the length of every code, up to the longest code a symbol code can hold
+    pub fn new(max_code_len: u8) -> HuffmanCodec {
+        HuffmanCodec { max_code_len: max_code_len.min(MAX_CODE_LEN) }
+    }
+}
+
+impl Default for HuffmanCodec {
+    fn default() -> HuffmanCodec {
+        HuffmanCodec::new(DEFAULT_MAX_CODE_LEN)
+    }
+}
+
+impl Codec for HuffmanCodec {
+    fn method(&self) -> u8 {
+        METHOD_HUFFMAN
+    }
+
+    fn name(&self) -> &'static str {
+        "huffman"
+    }
+
+    fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>> {
+        let freq_table = create_freq_table(input)?;
+        let tree = create_code_tree(&freq_table, self.max_code_len);
+        let symbol_table = create_code_table(&tree, TABLE_SIZE);
+        // calculate the bit size for the compressed data
+        let mut data_bit_size = 0;
+        for (freq, symbol_code) in freq_table.iter().zip(&symbol_table) {
+            data_bit_size += freq * (symbol_code.bit_len as u64);
+        }
+        Ok(Box::new(CodeBook {
+            symbol_table,
+            data_bit_size,
+            byte_size: freq_table.iter().sum()
+        }))
+    }
+
+    fn create_decoder(&self, reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>> {
+        Ok(Box::new(HuffmanEntryDecoder {
+            table: read_code_lengths(reader, TABLE_SIZE)?,
+            remaining: block.original_byte_size
+        }))
+    }
+}
+
+// the codes for compressing a single file
+pub struct CodeBook {
+    pub symbol_table: Vec<SymbolCode>,
+    // the bit size of the file once compressed
+    data_bit_size: u64,
+    // the byte size of the file the codes were built for
+    byte_size: u64
+}
+
+impl EntryEncoder for CodeBook {
+    fn header_bit_size(&self) -> u64 {
+        code_lengths_bit_size(&self.symbol_table)
+    }
+
+    fn data_bit_size(&self) -> Option<u64> {
+        Some(self.data_bit_size)
+    }
+
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()> {
+        write_code_lengths(writer, &self.symbol_table)?;
+        // stop at the size the codes were built for in case the file has grown since
+        compress_file(input.take(self.byte_size), writer, &self.symbol_table)
+    }
+}
+
+// the codes for compressing a single file into a solid stream, the code table is written once ahead of the stream
+pub struct SolidCodeBook {
+    pub symbol_table: Rc<[SymbolCode]>,
+    // the bit size of the file once compressed
+    data_bit_size: u64,
+    // the byte size of the file the codes were counted for
+    byte_size: u64
+}
+
+impl SolidCodeBook {
+    pub fn new(symbol_table: Rc<[SymbolCode]>, freq_table: &[u64]) -> SolidCodeBook {
+        let mut data_bit_size = 0;
+        for (freq, symbol_code) in freq_table.iter().zip(symbol_table.iter()) {
+            data_bit_size += freq * (symbol_code.bit_len as u64);
+        }
+        SolidCodeBook { symbol_table, data_bit_size, byte_size: freq_table.iter().sum() }
+    }
+
+    // whether the file takes fewer bits coded than stored, empty files always belong to the stream
+    pub fn shrinks(&self) -> bool {
+        self.data_bit_size <= self.byte_size * 8
+    }
+}
+
+impl EntryEncoder for SolidCodeBook {
+    fn header_bit_size(&self) -> u64 {
+        0
+    }
+
+    fn data_bit_size(&self) -> Option<u64> {
+        Some(self.data_bit_size)
+    }
+
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()> {
+        compress_file(input.take(self.byte_size), writer, &self.symbol_table)
+    }
+}
+
+pub(crate) struct HuffmanEntryDecoder {
+    table: DecodeTable,
+    // the number of bytes left to decode
+    remaining: u64
+}
+
+impl HuffmanEntryDecoder {
+    pub(crate) fn new(table: DecodeTable, remaining: u64) -> HuffmanEntryDecoder {
+        HuffmanEntryDecoder { table, remaining }
+    }
+}
+
+impl EntryDecoder for HuffmanEntryDecoder {
+    fn decode(&mut self, reader: &mut BitReader<dyn Read + '_>, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() && self.remaining > 0 {
+            buf[len] = self.table.decode_next_symbol(reader)? as u8;
+            self.remaining -= 1;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+// the code lengths are stored as the number of used symbols, the gap from each used symbol to the
+// next as an elias gamma code, the bit width of the lengths, then the length of each used symbol
+// the number of used symbols takes as many bits as the largest symbol, 8 bits for bytes
+pub(crate) fn write_code_lengths<W: Write + ?Sized>(writer: &mut BitWriter<W>, symbol_table: &[SymbolCode]) -> io::Result<()> {
+    let symbols: Vec<usize> = used_symbols(symbol_table).collect();
+    writer.write_bits_u32((symbols.len() - 1) as u32, symbol_width(symbol_table.len()))?;
+    let mut prev_symbol = 0;
+    for &symbol in &symbols {
+        // the first gap is counted from one before symbol zero so every gap is at least one
+        write_gamma(writer, (symbol + 1 - prev_symbol) as u32)?;
+        prev_symbol = symbol + 1;
+    }
+    let len_width = code_len_width(symbol_table);
+    writer.write_bits(len_width - 1, 3)?;
+    for symbol in symbols {
+        writer.write_bits(symbol_table[symbol].bit_len, len_width)?;
+    }
+    Ok(())
+}
+
+// writes the number's bits after one less zero bit than there are bits, so small numbers are short
+pub(crate) fn write_gamma<W: Write + ?Sized>(writer: &mut BitWriter<W>, num: u32) -> io::Result<()> {
+    let bit_len = 32 - num.leading_zeros();
+    for _ in 1..bit_len {
+        writer.write_bit(0)?;
+    }
+    for i in (0..bit_len).rev() {
+        writer.write_bit(bitwise::get_bit(num, i))?;
+    }
+    Ok(())
+}
+
+pub(crate) fn gamma_bit_size(num: u32) -> u64 {
+    (2 * (32 - num.leading_zeros()) - 1) as u64
+}
+
+pub(crate) fn code_lengths_bit_size(symbol_table: &[SymbolCode]) -> u64 {
+    let mut bit_size = symbol_width(symbol_table.len()) as u64 + 3;
+    let mut prev_symbol = 0;
+    for symbol in used_symbols(symbol_table) {
+        bit_size += gamma_bit_size((symbol + 1 - prev_symbol) as u32) + code_len_width(symbol_table) as u64;
+        prev_symbol = symbol + 1;
+    }
+    bit_size
+}
+
+fn used_symbols(symbol_table: &[SymbolCode]) -> impl Iterator<Item = usize> + '_ {
+    symbol_table
+        .iter()
+        .enumerate()
+        .filter(|(_, symbol_code)| symbol_code.bit_len > 0)
+        .map(|(symbol, _)| symbol)
+}
+
+// the number of bits needed to store the largest symbol in an alphabet
+fn symbol_width(table_size: usize) -> u8 {
+    (usize::BITS - (table_size - 1).leading_zeros()) as u8
+}
+
+// the number of bits needed to store the longest code length
+fn code_len_width(symbol_table: &[SymbolCode]) -> u8 {
+    let max_len = symbol_table.iter().map(|symbol_code| symbol_code.bit_len).max().unwrap_or(0);
+    (8 - max_len.leading_zeros() as u8).max(1)
+}
+
+pub(crate) fn compress_file<R: Read, W: Write + ?Sized>(input: R, writer: &mut BitWriter<W>, symbol_table: &[SymbolCode]) -> io::Result<()> {
+    let mut reader = BitReader::new(input);
+    while !reader.eof()? {
+        let byte = reader.read_byte()?;
+        writer.write_symbol(&symbol_table[byte as usize])?;
+    }
+    Ok(())
+}
+
+pub(crate) fn create_freq_table<R: Read>(input: R) -> io::Result<Vec<u64>> {
+    let mut freq_table = vec![0u64; TABLE_SIZE];
+
+    // iterate through each byte in the input and increment count
+    let mut reader = BitReader::new(input);
+    while !reader.eof()? {
+        let byte = reader.read_byte()?;
+        freq_table[usize::from(byte)] += 1;
+    }
+
+    Ok(freq_table)
+}
+
+// builds a tree with the shortest total code length where no code is longer than the limit
+// the limit is raised when it is too short to give every used symbol its own code
+pub(crate) fn create_code_tree(freq_table: &[u64], max_code_len: u8) -> Tree {
+    let mut leaves = vec![];
+    for (i, &freq) in freq_table.iter().enumerate() {
+        if freq != 0 {
+            leaves.push((freq, i));
+        }
+    }
+
+    // pad with unused symbols so empty or single symbol files still get a code of at least one bit
+    let mut unused_symbol = 0;
+    while leaves.len() < 2 {
+        if freq_table[unused_symbol] == 0 {
+            leaves.push((0, unused_symbol));
+        }
+        unused_symbol += 1;
+    }
+
+    let min_code_len = (usize::BITS - (leaves.len() - 1).leading_zeros()) as u8;
+    let code_lens = create_code_lens(&mut leaves, max_code_len.max(min_code_len));
+
+    // symbols are placed in the tree in order of code length then symbol, the order of canonical codes
+    let mut symbols: Vec<(u8, usize)> = leaves.iter().map(|&(_, i)| (code_lens[i], i)).collect();
+    symbols.sort();
+    let mut tree = Tree::default();
+    tree.root = create_code_node(&mut tree, freq_table, 0, &mut symbols.into_iter().peekable());
+    tree
+}
+
+// package-merge algorithm: the cheapest way to pick 2n - 2 items from the symbols repeated at each
+// of the allowed code lengths, where pairs of items are packaged into an item for the length above,
+// gives each symbol a code as long as the number of times it was picked
+fn create_code_lens(leaves: &mut [(u64, usize)], max_code_len: u8) -> Vec<u8> {
+    leaves.sort();
+    // each item is its weight and the symbols packaged into it
+    let leaf_items: Vec<(u128, Vec<usize>)> = leaves
+        .iter()
+        .map(|&(freq, i)| (freq as u128, vec![i]))
+        .collect();
+
+    let mut items = leaf_items.clone();
+    for _ in 1..max_code_len {
+        let packages = items
+            .chunks_exact(2)
+            .map(|pair| (pair[0].0 + pair[1].0, [pair[0].1.as_slice(), pair[1].1.as_slice()].concat()));
+        // merge the packages with the leaves, keeping the items sorted by weight
+        let mut merged = Vec::with_capacity(leaf_items.len() + items.len() / 2);
+        let mut leaf_iter = leaf_items.iter().cloned().peekable();
+        for package in packages {
+            while let Some(leaf) = leaf_iter.next_if(|leaf| leaf.0 <= package.0) {
+                merged.push(leaf);
+            }
+            merged.push(package);
+        }
+        merged.extend(leaf_iter);
+        items = merged;
+    }
+
+    let mut code_lens = vec![0u8; leaves.iter().map(|&(_, i)| i + 1).max().unwrap_or(0)];
+    for (_, symbols) in items.iter().take(2 * leaves.len() - 2) {
+        for &i in symbols {
+            code_lens[i] += 1;
+        }
+    }
+    code_lens
+}
+
+// builds the subtree at the depth from symbols in canonical order, placing each at its code length
+fn create_code_node<I: Iterator<Item = (u8, usize)>>(tree: &mut Tree, freq_table: &[u64], depth: u8, symbols: &mut Peekable<I>) -> usize {
+    if let Some((_, i)) = symbols.next_if(|&(code_len, _)| code_len == depth) {
+        return tree.add_leaf(i as u16, freq_table[i]);
+    }
+    let left = create_code_node(tree, freq_table, depth + 1, symbols);
+    let right = create_code_node(tree, freq_table, depth + 1, symbols);
+    let w = tree.nodes[left].weight + tree.nodes[right].weight;
+    tree.add_internal(left, right, 0, w)
+}
+
+fn walk_code_tree(tree: &Tree, index: usize, mut symbol_code: SymbolCode, symbol_table: &mut [SymbolCode]) {
+    let node = &tree.nodes[index];
+    if node.is_leaf() {
+        symbol_code.plain_symbol = node.plain_symbol;
+        symbol_table[usize::from(node.plain_symbol)] = symbol_code;
+    }
+    if let Some(left) = node.left {
+        let symbol_code = symbol_code.append_bit(0);
+        walk_code_tree(tree, left, symbol_code, symbol_table);
+    }
+    if let Some(right) = node.right {
+        let symbol_code = symbol_code.append_bit(1);
+        walk_code_tree(tree, right, symbol_code, symbol_table);
+    }
+}
+
+// the tree only decides the length of each code, the codes themselves are canonical
+pub(crate) fn create_code_table(tree: &Tree, table_size: usize) -> Vec<SymbolCode> {
+    let symbol_code = SymbolCode::new();
+    let mut symbol_table = vec![symbol_code; table_size];
+    walk_code_tree(tree, tree.root, symbol_code, &mut symbol_table);
+    let code_lens: Vec<u8> = symbol_table.iter().map(|symbol_code| symbol_code.bit_len).collect();
+    canonical::create_canonical_table(&code_lens)
+}
+
+// reads the code length table written by the compressor into a table for decoding
+pub(crate) fn read_code_lengths<R: Read + ?Sized>(reader: &mut BitReader<R>, table_size: usize) -> Result<DecodeTable> {
+    let symbol_count = reader.read_bits_u32(symbol_width(table_size)).map_err(tree_error)? as usize + 1;
+    let mut symbols = vec![];
+    let mut next_symbol = 0;
+    for _ in 0..symbol_count {
+        let symbol = next_symbol + read_gamma(reader, table_size)? as usize - 1;
+        if symbol >= table_size {
+            return Err(ZipperError::MalformedTree);
+        }
+        symbols.push(symbol);
+        next_symbol = symbol + 1;
+    }
+    let len_width = reader.read_bits(3).map_err(tree_error)? + 1;
+    let mut code_lens = vec![0u8; table_size];
+    for symbol in symbols {
+        code_lens[symbol] = reader.read_bits(len_width).map_err(tree_error)?;
+    }
+    // lengths that leave gaps or overlap can't be decoded
+    if !is_complete_code(&code_lens) {
+        return Err(ZipperError::MalformedTree);
+    }
+    Ok(DecodeTable::new(&code_lens))
+}
+
+pub(crate) fn read_gamma<R: Read + ?Sized>(reader: &mut BitReader<R>, table_size: usize) -> Result<u32> {
+    // gaps between symbols are never larger than the alphabet
+    let mut bit_len = 1;
+    while reader.read_bit().map_err(tree_error)? == 0 {
+        bit_len += 1;
+        if bit_len > usize::BITS - table_size.leading_zeros() {
+            return Err(ZipperError::MalformedTree);
+        }
+    }
+    let mut num = 1;
+    for _ in 1..bit_len {
+        num = (num << 1) | reader.read_bit().map_err(tree_error)? as u32;
+    }
+    Ok(num)
+}
+
+// a tree that runs past the end of the archive is malformed rather than an i/o failure
+pub(crate) fn tree_error(err: io::Error) -> ZipperError {
+    match err.kind() {
+        ErrorKind::UnexpectedEof => ZipperError::MalformedTree,
+        _ => ZipperError::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code_lens(freq_table: &[u64], max_code_len: u8) -> Vec<u8> {
+        create_code_table(&create_code_tree(freq_table, max_code_len), freq_table.len()).iter().map(|code| code.bit_len).collect()
+    }
+
+    #[test]
+    fn code_lengths_stay_within_the_limit() {
+        // fibonacci frequencies give the most skewed tree possible, one level deeper for each symbol
+        let mut freq_table = vec![0u64; TABLE_SIZE];
+        let (mut a, mut b) = (1, 1);
+        for freq in freq_table.iter_mut().take(30) {
+            *freq = a;
+            (a, b) = (b, a + b);
+        }
+        let cost = |lens: &[u8]| lens.iter().zip(&freq_table).map(|(&len, &freq)| len as u64 * freq).sum::<u64>();
+
+        let unlimited = code_lens(&freq_table, MAX_CODE_LEN);
+        assert_eq!(unlimited.iter().max(), Some(&29));
+        let limited = code_lens(&freq_table, DEFAULT_MAX_CODE_LEN);
+        assert_eq!(limited.iter().max(), Some(&DEFAULT_MAX_CODE_LEN));
+        assert!(canonical::is_complete_code(&limited));
+        assert!(cost(&limited) >= cost(&unlimited));
+
+        // a limit too short for every symbol to have its own code is raised to the shortest that fits
+        let limited = code_lens(&[1; TABLE_SIZE], 4);
+        assert!(limited.iter().all(|&len| len == 8));
+    }
+}
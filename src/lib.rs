@@ -0,0 +1,47 @@
+// Joseph Prichard
+// 10/18/2026
+// Library interface for reading and writing zipr archives
+
+mod compress;
+mod read;
+mod decompress;
+mod bitwise;
+mod write;
+mod tree;
+// helpers called by hand while debugging, nothing calls them otherwise
+#[allow(dead_code)]
+mod debug;
+mod block;
+mod charset;
+mod utils;
+mod error;
+mod checksum;
+mod canonical;
+mod codec;
+mod huffman;
+mod lz77;
+mod adaptive;
+mod chunked;
+mod context;
+mod rans;
+mod bwt;
+mod cluster;
+mod dictionary;
+mod sha256;
+
+pub use adaptive::{AdaptiveCodec, AdaptiveDecoder, AdaptiveEncoder};
+pub use block::{list_file_blocks, list_file_metadata, list_huffman_gains, ArchiveHeader, EntryType, FileBlock};
+pub use bwt::BwtCodec;
+pub use chunked::ChunkedCodec;
+pub use context::ContextCodec;
+pub use dictionary::{Dictionary, DictionaryCodec};
+pub use rans::RansCodec;
+pub use codec::{Codec, CodecRegistry, EntryDecoder, EntryEncoder};
+pub use huffman::{HuffmanCodec, DEFAULT_MAX_CODE_LEN};
+pub use lz77::Lz77Codec;
+pub use compress::{compress_bytes, ArchiveWriter, HuffmanEncoder};
+pub use decompress::{decompress_bytes, ArchiveReader, EntryReader, HuffmanDecoder};
+pub use error::{Result, ZipperError};
+pub use read::{BitReader, FileReader};
+pub use sha256::{hash_reader, Sha256};
+pub use write::{BitWriter, FileWriter};
@@ -0,0 +1,444 @@
+// Joseph Prichard
+// 10/18/2026
+// LZ77 codec, replacing repeated strings with matches against a sliding window that are then huffman coded
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use crate::bitwise::SymbolCode;
+use crate::block::FileBlock;
+use crate::canonical::{DecodeTable, MAX_CODE_LEN};
+use crate::codec::{Codec, EntryDecoder, EntryEncoder, METHOD_LZ77};
+use crate::error::Result;
+use crate::huffman;
+use crate::huffman::DEFAULT_MAX_CODE_LEN;
+use crate::read::BitReader;
+use crate::write::BitWriter;
+
+// how far back a match can reach
+const WINDOW_SIZE: usize = 1 << 15;
+const MIN_MATCH: usize = 3;
+const MAX_MATCH: usize = 258;
+const HASH_BITS: u32 = 15;
+// the most earlier positions compared when looking for a match
+const MAX_CHAIN: usize = 128;
+// matches at least this long are taken without checking for a longer match at the next byte
+const LAZY_LIMIT: usize = 32;
+// the byte size of reads from the input
+const CHUNK_SIZE: usize = 1 << 16;
+
+// literal bytes are symbols 0 to 255, followed by a symbol for each range of match lengths
+const LITERAL_COUNT: usize = 256;
+const LITLEN_TABLE_SIZE: usize = LITERAL_COUNT + LENGTH_BASES.len();
+const DIST_TABLE_SIZE: usize = DIST_BASES.len();
+
+// the shortest length or distance of each symbol's range, and the extra bits that pick one from the range
+const LENGTH_BASES: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258
+];
+const LENGTH_EXTRA_BITS: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0
+];
+const DIST_BASES: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577
+];
+const DIST_EXTRA_BITS: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13
+];
+
+pub struct Lz77Codec {
+    // the longest code any symbol is given
+    max_code_len: u8
+}
+
+impl Lz77Codec {
+    // limits the length of every code, up to the longest code a symbol code can hold
+    pub fn new(max_code_len: u8) -> Lz77Codec {
+        Lz77Codec { max_code_len: max_code_len.min(MAX_CODE_LEN) }
+    }
+}
+
+impl Default for Lz77Codec {
+    fn default() -> Lz77Codec {
+        Lz77Codec::new(DEFAULT_MAX_CODE_LEN)
+    }
+}
+
+impl Codec for Lz77Codec {
+    fn method(&self) -> u8 {
+        METHOD_LZ77
+    }
+
+    fn name(&self) -> &'static str {
+        "lz77"
+    }
+
+    fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>> {
+        // count the symbols the matcher emits, the same symbols are emitted again when encoding
+        let mut litlen_freq_table = vec![0u64; LITLEN_TABLE_SIZE];
+        let mut dist_freq_table = vec![0u64; DIST_TABLE_SIZE];
+        let mut extra_bit_size = 0;
+        let mut matcher = Matcher::new(input);
+        while let Some(token) = matcher.next_token()? {
+            match token {
+                Token::Literal(byte) => litlen_freq_table[byte as usize] += 1,
+                Token::Match(len, dist) => {
+                    let length_code = length_code(len);
+                    let dist_code = dist_code(dist);
+                    litlen_freq_table[LITERAL_COUNT + length_code] += 1;
+                    dist_freq_table[dist_code] += 1;
+                    extra_bit_size += (LENGTH_EXTRA_BITS[length_code] + DIST_EXTRA_BITS[dist_code]) as u64;
+                }
+            }
+        }
+
+        let litlen_table = create_symbol_table(&litlen_freq_table, self.max_code_len);
+        let dist_table = create_symbol_table(&dist_freq_table, self.max_code_len);
+        let mut data_bit_size = extra_bit_size;
+        for (freq_table, symbol_table) in [(&litlen_freq_table, &litlen_table), (&dist_freq_table, &dist_table)] {
+            for (freq, symbol_code) in freq_table.iter().zip(symbol_table) {
+                data_bit_size += freq * (symbol_code.bit_len as u64);
+            }
+        }
+        Ok(Box::new(Lz77Encoder {
+            litlen_table,
+            dist_table,
+            data_bit_size,
+            byte_size: matcher.pos
+        }))
+    }
+
+    fn create_decoder(&self, reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>> {
+        let litlen_table = huffman::read_code_lengths(reader, LITLEN_TABLE_SIZE)?;
+        let dist_table = huffman::read_code_lengths(reader, DIST_TABLE_SIZE)?;
+        Ok(Box::new(Lz77EntryDecoder {
+            litlen_table,
+            dist_table,
+            history: vec![0; WINDOW_SIZE],
+            written: 0,
+            match_len: 0,
+            match_dist: 0,
+            remaining: block.original_byte_size
+        }))
+    }
+}
+
+fn create_symbol_table(freq_table: &[u64], max_code_len: u8) -> Vec<SymbolCode> {
+    let tree = huffman::create_code_tree(freq_table, max_code_len);
+    huffman::create_code_table(&tree, freq_table.len())
+}
+
+// the index of the range the value falls in
+fn length_code(len: usize) -> usize {
+    LENGTH_BASES.partition_point(|&base| base as usize <= len) - 1
+}
+
+fn dist_code(dist: usize) -> usize {
+    DIST_BASES.partition_point(|&base| base as usize <= dist) - 1
+}
+
+struct Lz77Encoder {
+    litlen_table: Vec<SymbolCode>,
+    dist_table: Vec<SymbolCode>,
+    // the bit size of the file once compressed
+    data_bit_size: u64,
+    // the byte size of the file the codes were built for
+    byte_size: u64
+}
+
+impl EntryEncoder for Lz77Encoder {
+    fn header_bit_size(&self) -> u64 {
+        huffman::code_lengths_bit_size(&self.litlen_table) + huffman::code_lengths_bit_size(&self.dist_table)
+    }
+
+    fn data_bit_size(&self) -> Option<u64> {
+        Some(self.data_bit_size)
+    }
+
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()> {
+        huffman::write_code_lengths(writer, &self.litlen_table)?;
+        huffman::write_code_lengths(writer, &self.dist_table)?;
+        // stop at the size the codes were built for in case the file has grown since
+        let mut matcher = Matcher::new(input.take(self.byte_size));
+        while let Some(token) = matcher.next_token()? {
+            match token {
+                Token::Literal(byte) => writer.write_symbol(&self.litlen_table[byte as usize])?,
+                Token::Match(len, dist) => {
+                    let length_code = length_code(len);
+                    writer.write_symbol(&self.litlen_table[LITERAL_COUNT + length_code])?;
+                    writer.write_bits_u32((len - LENGTH_BASES[length_code] as usize) as u32, LENGTH_EXTRA_BITS[length_code])?;
+                    let dist_code = dist_code(dist);
+                    writer.write_symbol(&self.dist_table[dist_code])?;
+                    writer.write_bits_u32((dist - DIST_BASES[dist_code] as usize) as u32, DIST_EXTRA_BITS[dist_code])?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+enum Token {
+    Literal(u8),
+    // the length of the match and how far back it starts
+    Match(usize, usize)
+}
+
+// finds matches for the input against the bytes before it, using hash chains of earlier positions
+struct Matcher<R: Read> {
+    input: R,
+    // the bytes from the start of the window to the end of the last read
+    buffer: Vec<u8>,
+    // the position in the input of the first byte in the buffer
+    base: u64,
+    // the position in the input of the next token
+    pos: u64,
+    eof: bool,
+    // the most recent position plus one for each hash of three bytes, zero for none
+    head: Vec<u64>,
+    // the previous position plus one with the same hash, for each position in the window
+    prev: Vec<u64>
+}
+
+impl<R: Read> Matcher<R> {
+    fn new(input: R) -> Matcher<R> {
+        Matcher {
+            input,
+            buffer: vec![],
+            base: 0,
+            pos: 0,
+            eof: false,
+            head: vec![0; 1 << HASH_BITS],
+            prev: vec![0; WINDOW_SIZE]
+        }
+    }
+
+    fn next_token(&mut self) -> io::Result<Option<Token>> {
+        self.fill_buffer()?;
+        let pos = self.pos;
+        if self.available(pos) == 0 {
+            return Ok(None);
+        }
+
+        let (len, dist) = self.find_match(pos);
+        self.insert(pos);
+        // a longer match starting at the next byte is worth emitting this byte as a literal
+        if len >= MIN_MATCH && (len >= LAZY_LIMIT || self.find_match(pos + 1).0 <= len) {
+            for match_pos in pos + 1..pos + len as u64 {
+                self.insert(match_pos);
+            }
+            self.pos += len as u64;
+            return Ok(Some(Token::Match(len, dist)));
+        }
+        self.pos += 1;
+        Ok(Some(Token::Literal(self.buffer[(pos - self.base) as usize])))
+    }
+
+    // reads until a full match and the byte after it are buffered, dropping bytes that left the window
+    fn fill_buffer(&mut self) -> io::Result<()> {
+        let needed = (self.pos - self.base) as usize + MAX_MATCH + 1;
+        while !self.eof && self.buffer.len() < needed {
+            let start = self.buffer.len();
+            self.buffer.resize(start + CHUNK_SIZE, 0);
+            let read_size = loop {
+                match self.input.read(&mut self.buffer[start..]) {
+                    Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                    result => break result?
+                }
+            };
+            self.buffer.truncate(start + read_size);
+            self.eof = read_size == 0;
+        }
+        let consumed = (self.pos - self.base) as usize;
+        if consumed > WINDOW_SIZE + CHUNK_SIZE {
+            self.buffer.drain(..consumed - WINDOW_SIZE);
+            self.base = self.pos - WINDOW_SIZE as u64;
+        }
+        Ok(())
+    }
+
+    fn available(&self, pos: u64) -> usize {
+        self.buffer.len().saturating_sub((pos - self.base) as usize)
+    }
+
+    fn hash(&self, pos: u64) -> usize {
+        let i = (pos - self.base) as usize;
+        let bytes = &self.buffer[i..i + MIN_MATCH];
+        (((bytes[0] as usize) << 10) ^ ((bytes[1] as usize) << 5) ^ bytes[2] as usize) & ((1 << HASH_BITS) - 1)
+    }
+
+    fn insert(&mut self, pos: u64) {
+        if self.available(pos) >= MIN_MATCH {
+            let hash = self.hash(pos);
+            self.prev[pos as usize % WINDOW_SIZE] = self.head[hash];
+            self.head[hash] = pos + 1;
+        }
+    }
+
+    // the longest match for the bytes at the position, or a zero length when none are long enough
+    fn find_match(&self, pos: u64) -> (usize, usize) {
+        let max_len = self.available(pos).min(MAX_MATCH);
+        if max_len < MIN_MATCH {
+            return (0, 0);
+        }
+        let current = &self.buffer[(pos - self.base) as usize..][..max_len];
+
+        let mut best = (0, 0);
+        let mut candidate = self.head[self.hash(pos)];
+        for _ in 0..MAX_CHAIN {
+            // a chain ends at an empty entry, or once it leaves the window
+            if candidate == 0 || pos - (candidate - 1) > WINDOW_SIZE as u64 {
+                break;
+            }
+            let match_pos = candidate - 1;
+            let earlier = &self.buffer[(match_pos - self.base) as usize..];
+            let len = current.iter().zip(earlier).take_while(|(a, b)| a == b).count();
+            if len > best.0 {
+                best = (len, (pos - match_pos) as usize);
+                if len == max_len {
+                    break;
+                }
+            }
+            // entries can be overwritten by newer positions, which would loop the chain forward
+            let next = self.prev[match_pos as usize % WINDOW_SIZE];
+            if next >= candidate {
+                break;
+            }
+            candidate = next;
+        }
+        if best.0 >= MIN_MATCH { best } else { (0, 0) }
+    }
+}
+
+struct Lz77EntryDecoder {
+    litlen_table: DecodeTable,
+    dist_table: DecodeTable,
+    // the last window of decoded bytes, written around in a circle
+    history: Vec<u8>,
+    // the number of bytes decoded so far
+    written: u64,
+    // the bytes left to copy for the match being decoded, and how far back it starts
+    match_len: usize,
+    match_dist: usize,
+    // the number of bytes left to decode
+    remaining: u64
+}
+
+impl EntryDecoder for Lz77EntryDecoder {
+    fn decode(&mut self, reader: &mut BitReader<dyn Read + '_>, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() && self.remaining > 0 {
+            let byte = if self.match_len > 0 {
+                self.match_len -= 1;
+                self.history[(self.written as usize - self.match_dist) % WINDOW_SIZE]
+            } else {
+                let symbol = self.litlen_table.decode_next_symbol(reader)? as usize;
+                if symbol < LITERAL_COUNT {
+                    symbol as u8
+                } else {
+                    self.read_match(reader, symbol - LITERAL_COUNT)?;
+                    continue;
+                }
+            };
+            self.history[self.written as usize % WINDOW_SIZE] = byte;
+            self.written += 1;
+            self.remaining -= 1;
+            buf[len] = byte;
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+impl Lz77EntryDecoder {
+    fn read_match(&mut self, reader: &mut BitReader<dyn Read + '_>, length_code: usize) -> io::Result<()> {
+        self.match_len = LENGTH_BASES[length_code] as usize + reader.read_bits_u32(LENGTH_EXTRA_BITS[length_code])? as usize;
+        let dist_code = self.dist_table.decode_next_symbol(reader)? as usize;
+        self.match_dist = DIST_BASES[dist_code] as usize + reader.read_bits_u32(DIST_EXTRA_BITS[dist_code])? as usize;
+        if self.match_dist as u64 > self.written || self.match_dist > WINDOW_SIZE {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Match starts before the start of the entry"));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::compress::tests::noise;
+    use super::*;
+
+    fn tokens(input: &[u8]) -> Vec<(usize, usize)> {
+        let mut matcher = Matcher::new(input);
+        let mut tokens = vec![];
+        while let Some(token) = matcher.next_token().unwrap() {
+            tokens.push(match token {
+                Token::Literal(byte) => (byte as usize, 0),
+                Token::Match(len, dist) => (len, dist)
+            });
+        }
+        tokens
+    }
+
+    fn round_trip(codec: &Lz77Codec, input: &[u8]) -> (Vec<u8>, u64) {
+        let encoder = codec.create_encoder(&mut &input[..]).unwrap();
+        let mut encoded = vec![];
+        let mut writer = BitWriter::new(&mut encoded);
+        encoder.encode(&mut &input[..], &mut writer).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut block = FileBlock::new(b"entry", Path::new(""));
+        block.original_byte_size = input.len() as u64;
+        let mut reader = BitReader::new(encoded.as_slice());
+        let mut decoder = codec.create_decoder(&mut reader, &block).unwrap();
+        let mut decoded = vec![];
+        let mut buf = [0; 1000];
+        loop {
+            match decoder.decode(&mut reader, &mut buf).unwrap() {
+                0 => return (decoded, encoder.header_bit_size() + encoder.data_bit_size().unwrap()),
+                len => decoded.extend_from_slice(&buf[..len])
+            }
+        }
+    }
+
+    #[test]
+    fn lengths_and_distances_fall_in_their_ranges() {
+        for len in MIN_MATCH..=MAX_MATCH {
+            let code = length_code(len);
+            assert!(len - (LENGTH_BASES[code] as usize) < 1 << LENGTH_EXTRA_BITS[code], "length {}", len);
+        }
+        for dist in 1..=WINDOW_SIZE {
+            let code = dist_code(dist);
+            assert!(dist - (DIST_BASES[code] as usize) < 1 << DIST_EXTRA_BITS[code], "distance {}", dist);
+        }
+        assert_eq!((length_code(3), length_code(257), length_code(258)), (0, 27, 28));
+        assert_eq!((dist_code(1), dist_code(WINDOW_SIZE)), (0, 29));
+    }
+
+    #[test]
+    fn repeated_strings_become_matches() {
+        let (a, b, c) = (b'a' as usize, b'b' as usize, b'c' as usize);
+        assert_eq!(tokens(b"abcabcabcabc"), [(a, 0), (b, 0), (c, 0), (9, 3)]);
+        // a run copies from the byte just before it, overlapping the bytes it writes
+        assert_eq!(tokens(&[b'a'; 300]), [(a, 0), (258, 1), (41, 1)]);
+        // strings shorter than the shortest match stay literals
+        assert_eq!(tokens(b"abxab"), [(a, 0), (b, 0), (b'x' as usize, 0), (a, 0), (b, 0)]);
+    }
+
+    #[test]
+    fn matches_reach_back_across_the_whole_window() {
+        // the noise repeats from the far end of the window, so only matches can compress it
+        let block = noise(WINDOW_SIZE - 100);
+        let input = [block.clone(), block, vec![0; 5000]].concat();
+        let (decoded, bit_size) = round_trip(&Lz77Codec::default(), &input);
+        assert_eq!(decoded, input);
+        assert!(bit_size < (WINDOW_SIZE as u64) * 8 + 8 * 1000);
+
+        // a short code limit still decodes
+        let (decoded, _) = round_trip(&Lz77Codec::new(9), &input);
+        assert_eq!(decoded, input);
+    }
+}
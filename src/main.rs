@@ -1,43 +1,186 @@
 use std::env;
-
-mod compress;
-mod read;
-mod decompress;
-mod bitwise;
-mod write;
-mod tree;
-mod debug;
-mod block;
-mod charset;
-mod utils;
+use std::fs::File;
+use std::path::PathBuf;
+use std::process;
+use std::time::Instant;
+use zipper::{ArchiveReader, ArchiveWriter, CodecRegistry, Dictionary, ZipperError, DEFAULT_MAX_CODE_LEN};
 
 fn main() {
-    let args: Vec<String> = env::args().collect();
-
-    let mut entries: Vec<String> = vec![];
+    let mut entries: Vec<PathBuf> = vec![];
+    // the method each entry is compressed with, if one was given before it
+    let mut entry_methods: Vec<Option<u8>> = vec![];
     let mut exec_flag: String = String::from("");
+    let mut verbose = false;
+    let mut solid = false;
+    let mut code_book_count = 1;
+    let mut dictionary_path = None;
+    let mut method = None;
 
-    for i in 1..args.len() {
-        let arg= &args[i];
-        if arg.chars().nth(0).unwrap() == '-' {
-            exec_flag = String::from(arg);
-        } else {
-            entries.push(String::from(arg));
+    // paths are kept as os strings so names that aren't valid unicode still work
+    let mut args = env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.to_str() {
+            Some("-v") => verbose = true,
+            // training is a command of its own rather than a flag, since it writes a dictionary instead of an archive
+            Some("train") if entries.is_empty() && exec_flag.is_empty() => exec_flag = String::from("train"),
+            // the trained dictionary files are compressed with, or entries are decoded with
+            Some("-D") => dictionary_path = args.next().map(PathBuf::from),
+            // files without their own method share one code table in a single stream
+            Some("-s") => solid = true,
+            // files in the solid stream are clustered into this many groups, each with its own code table
+            Some("-k") => {
+                let count = args.next().unwrap_or_default();
+                match count.to_string_lossy().parse::<u16>() {
+                    Ok(count) if count > 0 => code_book_count = count,
+                    _ => {
+                        eprintln!("Invalid code book count: {}", count.to_string_lossy());
+                        process::exit(1);
+                    }
+                }
+            },
+            // the compression method is given by name, such as "huffman" or "lz77", for the entries after it
+            Some("-m") => {
+                let name = args.next().unwrap_or_default();
+                match CodecRegistry::default().by_name(&name.to_string_lossy()) {
+                    Some(codec) => method = Some(codec.method()),
+                    None => {
+                        eprintln!("Unknown compression method: {}", name.to_string_lossy());
+                        process::exit(1);
+                    }
+                }
+            },
+            Some(flag) if flag.starts_with('-') => exec_flag = String::from(flag),
+            _ => {
+                entries.push(PathBuf::from(arg));
+                entry_methods.push(method);
+            }
         }
     }
 
-    if entries.len() < 1 {
-        println!("Needs at least one file path as an argument");
+    if entries.is_empty() {
+        eprintln!("Needs at least one file path as an argument");
+        process::exit(1);
+    }
+
+    if let Err(err) = run(&exec_flag, verbose, solid, code_book_count, dictionary_path, &entries, &entry_methods) {
+        eprintln!("Error: {}", err);
+        process::exit(exit_code(&err));
     }
+}
+
+fn run(
+    exec_flag: &str,
+    verbose: bool,
+    solid: bool,
+    code_book_count: u16,
+    dictionary_path: Option<PathBuf>,
+    entries: &[PathBuf],
+    entry_methods: &[Option<u8>]
+) -> zipper::Result<()> {
     let last = entries.len() - 1;
+    let dictionary = dictionary_path.map(Dictionary::open).transpose()?;
+    let open_archive = |dictionary: Option<Dictionary>| -> zipper::Result<ArchiveReader<File>> {
+        let mut archive = ArchiveReader::open(&entries[last])?;
+        if let Some(dictionary) = dictionary {
+            archive.set_dictionary(dictionary);
+        }
+        Ok(archive)
+    };
 
-    match exec_flag.as_str() {
+    match exec_flag {
+        "train" => {
+            // the dictionary is named after the first sample, like an archive
+            let mut dictionary_filename = entries[0].clone().into_os_string();
+            dictionary_filename.push(".zdict");
+            let dictionary = Dictionary::train(entries, DEFAULT_MAX_CODE_LEN)?;
+            dictionary.save(&dictionary_filename)?;
+            println!("Trained dictionary {:08x} into {}", dictionary.id(), dictionary_filename.to_string_lossy());
+        },
         "-l" => {
-            let blocks = &decompress::get_file_blocks(&entries[last]);
-            block::list_file_blocks(blocks);
+            let mut archive = open_archive(dictionary)?;
+            zipper::list_file_blocks(archive.entries());
+            // codecs that store the size huffman coding would take are compared against it
+            let mut huffman_bit_sizes = vec![];
+            for index in 0..archive.entries().len() {
+                // entries that can't be opened, such as ones with a corrupted header or a dictionary that wasn't given, are still listed
+                huffman_bit_sizes.push(archive.huffman_bit_size(index).unwrap_or(None));
+            }
+            if huffman_bit_sizes.iter().any(Option::is_some) {
+                zipper::list_huffman_gains(archive.entries(), &huffman_bit_sizes);
+            }
+            if verbose {
+                zipper::list_file_metadata(archive.entries());
+            }
+        },
+        "-t" => {
+            let mut archive = open_archive(dictionary)?;
+            // test every entry before reporting the first failure
+            let mut first_err = None;
+            for index in 0..archive.entries().len() {
+                let filename_rel = archive.entries()[index].display_name().into_owned();
+                match archive.test_entry(index) {
+                    Ok(()) => println!("{:>8}\t\t{}", "ok", filename_rel),
+                    Err(err) => {
+                        println!("{:>8}\t\t{}\t\t{}", "FAILED", filename_rel, err);
+                        first_err.get_or_insert(err);
+                    }
+                }
+            }
+            if let Some(err) = first_err {
+                return Err(err);
+            }
         },
-        "-d" => decompress::unarchive_zip(&entries[last]),
-        "-c" => compress::archive_dir(&entries),
-        _ => compress::archive_dir(&entries)
+        "-d" => {
+            let now = Instant::now();
+            let mut archive = open_archive(dictionary)?;
+            archive.extract_all(entries[last].with_extension(""))?;
+            // duplicates are extracted from the data of the file they duplicate
+            for block in archive.entries().iter().filter(|block| block.is_duplicate()) {
+                println!("{:>8}\t\t{}", "dedup", block.display_entry());
+            }
+            println!("Finished unzipping in {:.2?}", now.elapsed());
+        },
+        // compression is the default when no flag or "-c" is passed
+        _ => {
+            let now = Instant::now();
+            let mut archive_filename = entries[0].clone().into_os_string();
+            archive_filename.push(".zipr");
+            let mut archive = ArchiveWriter::create(archive_filename)?;
+            archive.set_solid(solid);
+            archive.set_code_book_count(code_book_count);
+            if let Some(dictionary) = dictionary {
+                archive.set_dictionary(dictionary);
+            }
+            for (entry, &method) in entries.iter().zip(entry_methods) {
+                match method {
+                    Some(method) => archive.add_entry_with_method(entry, method)?,
+                    None => archive.add_entry(entry)?
+                }
+            }
+            let blocks = archive.finish()?;
+            println!("Finished zipping in {:.2?}", now.elapsed());
+            zipper::list_file_blocks(&blocks);
+        }
+    }
+    Ok(())
+}
+
+// each kind of error exits with its own code, 1 is reserved for usage errors
+fn exit_code(err: &ZipperError) -> i32 {
+    match err {
+        ZipperError::Io(_) => 2,
+        ZipperError::BadSignature => 3,
+        ZipperError::TruncatedHeader => 4,
+        ZipperError::MalformedTree => 5,
+        ZipperError::BadOffset(_) => 6,
+        ZipperError::EntryNotFound(_) => 7,
+        ZipperError::UnsupportedVersion(_, _) => 8,
+        ZipperError::UnsupportedFlags(_) => 9,
+        ZipperError::ChecksumMismatch(_) => 10,
+        ZipperError::UnsafePath(_) => 11,
+        ZipperError::UnknownEntryType(_) => 12,
+        ZipperError::UnsupportedMethod(_) => 13,
+        ZipperError::MissingDictionary(_) => 14,
+        ZipperError::BadDictionary => 15
     }
 }
@@ -0,0 +1,336 @@
+// Joseph Prichard
+// 10/18/2026
+// rANS codec, coding bytes with fractional bits from normalized byte frequencies
+
+use std::io;
+use std::io::{ErrorKind, Read, Write};
+use crate::block::FileBlock;
+use crate::codec::{Codec, EntryDecoder, EntryEncoder, METHOD_RANS};
+use crate::error::{Result, ZipperError};
+use crate::huffman;
+use crate::huffman::{tree_error, DEFAULT_MAX_CODE_LEN, TABLE_SIZE};
+use crate::read::BitReader;
+use crate::write::BitWriter;
+
+// frequencies are scaled to sum to a power of two, the precision probabilities are coded with
+const PROB_BITS: u32 = 15;
+const PROB_SCALE: u32 = 1 << PROB_BITS;
+// the state is kept between this bound and 256 times it by moving bytes in and out
+const STATE_LOWER_BOUND: u32 = 1 << 23;
+// the bit width of the number of bytes with a frequency, from none to every byte
+const SYMBOL_COUNT_WIDTH: u8 = 9;
+// rANS codes in reverse, so the input is coded in chunks that each fit in memory
+const CHUNK_SIZE: u64 = 1 << 16;
+
+pub struct RansCodec;
+
+impl Codec for RansCodec {
+    fn method(&self) -> u8 {
+        METHOD_RANS
+    }
+
+    fn name(&self) -> &'static str {
+        "rans"
+    }
+
+    fn stores_huffman_bit_size(&self) -> bool {
+        true
+    }
+
+    fn create_encoder(&self, input: &mut dyn Read) -> io::Result<Box<dyn EntryEncoder>> {
+        let freq_table = huffman::create_freq_table(input)?;
+        // the size huffman coding would take, kept so listings can show the gain over it
+        let tree = huffman::create_code_tree(&freq_table, DEFAULT_MAX_CODE_LEN);
+        let symbol_table = huffman::create_code_table(&tree, TABLE_SIZE);
+        let mut huffman_bit_size = huffman::code_lengths_bit_size(&symbol_table);
+        for (freq, symbol_code) in freq_table.iter().zip(&symbol_table) {
+            huffman_bit_size += freq * (symbol_code.bit_len as u64);
+        }
+        Ok(Box::new(RansEncoder {
+            freqs: normalize_freqs(&freq_table),
+            huffman_bit_size,
+            byte_size: freq_table.iter().sum()
+        }))
+    }
+
+    fn create_decoder(&self, reader: &mut BitReader<dyn Read + '_>, block: &FileBlock) -> Result<Box<dyn EntryDecoder>> {
+        let huffman_bit_size = reader.read_u64().map_err(tree_error)?;
+        let symbol_count = reader.read_bits_u32(SYMBOL_COUNT_WIDTH).map_err(tree_error)? as usize;
+        let mut freqs = vec![0u32; TABLE_SIZE];
+        let mut next_symbol = 0;
+        for _ in 0..symbol_count {
+            let symbol = next_symbol + huffman::read_gamma(reader, TABLE_SIZE)? as usize - 1;
+            if symbol >= TABLE_SIZE {
+                return Err(ZipperError::MalformedTree);
+            }
+            freqs[symbol] = read_freq(reader)?;
+            next_symbol = symbol + 1;
+        }
+        reader.align_to_byte();
+        // the frequencies have to split the probability range exactly for every state to decode
+        let freq_sum: u32 = freqs.iter().sum();
+        if freq_sum != PROB_SCALE && !(freq_sum == 0 && block.original_byte_size == 0) {
+            return Err(ZipperError::MalformedTree);
+        }
+
+        // the symbol of each slot in the probability range
+        let cum_freqs = cumulative_freqs(&freqs);
+        let mut slot_symbols = vec![0u8; PROB_SCALE as usize];
+        for (symbol, &freq) in freqs.iter().enumerate() {
+            let start = cum_freqs[symbol] as usize;
+            slot_symbols[start..start + freq as usize].fill(symbol as u8);
+        }
+        Ok(Box::new(RansDecoder {
+            freqs,
+            cum_freqs,
+            slot_symbols,
+            state: 0,
+            chunk_remaining: 0,
+            remaining: block.original_byte_size,
+            huffman_bit_size
+        }))
+    }
+}
+
+// scales the frequencies to sum to the probability scale, keeping every used byte at a frequency of at least one
+fn normalize_freqs(freq_table: &[u64]) -> Vec<u32> {
+    let total: u64 = freq_table.iter().sum();
+    let mut freqs = vec![0u32; freq_table.len()];
+    if total == 0 {
+        return freqs;
+    }
+    for (freq, &count) in freqs.iter_mut().zip(freq_table) {
+        if count > 0 {
+            *freq = ((count as u128 * PROB_SCALE as u128 / total as u128) as u32).max(1);
+        }
+    }
+    // rounding leaves the sum a little off, which is made up by the most frequent byte where it costs the least
+    let mut sum: u32 = freqs.iter().sum();
+    while sum != PROB_SCALE {
+        let (largest, _) = freqs.iter().enumerate().max_by_key(|&(_, &freq)| freq).expect("Table has a used byte");
+        if sum > PROB_SCALE {
+            freqs[largest] -= 1;
+            sum -= 1;
+        } else {
+            freqs[largest] += 1;
+            sum += 1;
+        }
+    }
+    freqs
+}
+
+fn cumulative_freqs(freqs: &[u32]) -> Vec<u32> {
+    let mut cum_freqs = Vec::with_capacity(freqs.len());
+    let mut cum_freq = 0;
+    for &freq in freqs {
+        cum_freqs.push(cum_freq);
+        cum_freq += freq;
+    }
+    cum_freqs
+}
+
+fn read_freq(reader: &mut BitReader<dyn Read + '_>) -> Result<u32> {
+    // a frequency is never larger than the probability scale
+    let freq = huffman::read_gamma(reader, PROB_SCALE as usize + 1)?;
+    if freq > PROB_SCALE {
+        return Err(ZipperError::MalformedTree);
+    }
+    Ok(freq)
+}
+
+struct RansEncoder {
+    // the normalized frequency of each byte
+    freqs: Vec<u32>,
+    huffman_bit_size: u64,
+    // the byte size of the file the frequencies were counted for
+    byte_size: u64
+}
+
+impl RansEncoder {
+    fn used_symbols(&self) -> impl Iterator<Item = (usize, u32)> + '_ {
+        self.freqs.iter().enumerate().filter(|(_, &freq)| freq > 0).map(|(symbol, &freq)| (symbol, freq))
+    }
+
+    // codes a chunk from its last byte to its first, writing the state last so the decoder reads it first
+    fn encode_chunk(&self, chunk: &[u8], cum_freqs: &[u32], output: &mut Vec<u8>) {
+        output.clear();
+        let mut state = STATE_LOWER_BOUND;
+        for &byte in chunk.iter().rev() {
+            let freq = self.freqs[byte as usize];
+            // move bytes out until coding the symbol keeps the state under its upper bound
+            let state_max = ((STATE_LOWER_BOUND >> PROB_BITS) << 8) * freq;
+            while state >= state_max {
+                output.push(state as u8);
+                state >>= 8;
+            }
+            state = ((state / freq) << PROB_BITS) + state % freq + cum_freqs[byte as usize];
+        }
+        output.extend_from_slice(&state.to_le_bytes());
+        output.reverse();
+    }
+}
+
+impl EntryEncoder for RansEncoder {
+    // the huffman size, the used bytes as gaps like the symbols of a code table, their frequencies, then padding to a byte
+    fn header_bit_size(&self) -> u64 {
+        let mut bit_size = 64 + SYMBOL_COUNT_WIDTH as u64;
+        let mut next_symbol = 0;
+        for (symbol, freq) in self.used_symbols() {
+            bit_size += huffman::gamma_bit_size((symbol + 1 - next_symbol) as u32) + huffman::gamma_bit_size(freq);
+            next_symbol = symbol + 1;
+        }
+        bit_size.next_multiple_of(8)
+    }
+
+    // the number of bytes moved out of the state depends on every byte before them, so the data has to be coded to be measured
+    fn data_bit_size(&self) -> Option<u64> {
+        None
+    }
+
+    fn encode(&self, input: &mut dyn Read, writer: &mut BitWriter<dyn Write + '_>) -> io::Result<()> {
+        writer.write_u64(self.huffman_bit_size)?;
+        writer.write_bits_u32(self.used_symbols().count() as u32, SYMBOL_COUNT_WIDTH)?;
+        let mut next_symbol = 0;
+        for (symbol, freq) in self.used_symbols() {
+            huffman::write_gamma(writer, (symbol + 1 - next_symbol) as u32)?;
+            huffman::write_gamma(writer, freq)?;
+            next_symbol = symbol + 1;
+        }
+        writer.align_to_byte();
+
+        // stop at the size the frequencies were counted for in case the file has grown since
+        let cum_freqs = cumulative_freqs(&self.freqs);
+        let mut input = input.take(self.byte_size);
+        let mut chunk = vec![];
+        let mut output = vec![];
+        loop {
+            chunk.clear();
+            (&mut input).take(CHUNK_SIZE).read_to_end(&mut chunk)?;
+            if chunk.is_empty() {
+                break;
+            }
+            self.encode_chunk(&chunk, &cum_freqs, &mut output);
+            writer.write_bytes(&output)?;
+        }
+        Ok(())
+    }
+}
+
+struct RansDecoder {
+    freqs: Vec<u32>,
+    cum_freqs: Vec<u32>,
+    slot_symbols: Vec<u8>,
+    state: u32,
+    // the number of bytes left to decode in the current chunk and in the entry
+    chunk_remaining: u64,
+    remaining: u64,
+    huffman_bit_size: u64
+}
+
+impl EntryDecoder for RansDecoder {
+    fn decode(&mut self, reader: &mut BitReader<dyn Read + '_>, buf: &mut [u8]) -> io::Result<usize> {
+        let mut len = 0;
+        while len < buf.len() && self.remaining > 0 {
+            if self.chunk_remaining == 0 {
+                // the state was written last and reversed along with the rest of the chunk
+                self.state = reader.read_u32()?.swap_bytes();
+                if !(STATE_LOWER_BOUND..STATE_LOWER_BOUND << 8).contains(&self.state) {
+                    return Err(io::Error::new(ErrorKind::InvalidData, "Chunk starts with a state out of range"));
+                }
+                self.chunk_remaining = self.remaining.min(CHUNK_SIZE);
+            }
+            let slot = self.state & (PROB_SCALE - 1);
+            let byte = self.slot_symbols[slot as usize];
+            self.state = self.freqs[byte as usize] * (self.state >> PROB_BITS) + slot - self.cum_freqs[byte as usize];
+            // move bytes into the state until it is back over its lower bound
+            while self.state < STATE_LOWER_BOUND {
+                self.state = (self.state << 8) | reader.read_byte()? as u32;
+            }
+            buf[len] = byte;
+            self.chunk_remaining -= 1;
+            self.remaining -= 1;
+            len += 1;
+        }
+        Ok(len)
+    }
+
+    fn huffman_bit_size(&self) -> Option<u64> {
+        Some(self.huffman_bit_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use crate::compress::tests::noise;
+    use super::*;
+
+    // encodes then decodes the input, returning the decoded bytes, the bit size written, and the decoder's huffman size
+    fn round_trip(input: &[u8]) -> (Vec<u8>, u64, Option<u64>) {
+        let encoder = RansCodec.create_encoder(&mut &input[..]).unwrap();
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        encoder.encode(&mut &input[..], &mut writer).unwrap();
+        let bit_size = writer.write_len();
+        writer.flush().unwrap();
+        drop(writer);
+
+        let mut block = FileBlock::new(b"entry", Path::new(""));
+        block.original_byte_size = input.len() as u64;
+        let mut reader = BitReader::new(bytes.as_slice());
+        let mut decoder = RansCodec.create_decoder(&mut reader, &block).ok().unwrap();
+        assert_eq!(reader.read_len(), encoder.header_bit_size());
+        let mut decoded = vec![0; input.len()];
+        let mut len = 0;
+        while len < decoded.len() {
+            len += decoder.decode(&mut reader, &mut decoded[len..]).unwrap();
+        }
+        (decoded, bit_size, decoder.huffman_bit_size())
+    }
+
+    #[test]
+    fn frequencies_are_scaled_to_the_probability_range() {
+        // one rare byte among many common ones still keeps a slot of its own
+        let mut freq_table = vec![0u64; TABLE_SIZE];
+        freq_table[b'a' as usize] = 1_000_000;
+        freq_table[b'b' as usize] = 1;
+        freq_table[b'c' as usize] = 3;
+        let freqs = normalize_freqs(&freq_table);
+        assert_eq!(freqs.iter().sum::<u32>(), PROB_SCALE);
+        assert_eq!((freqs[b'b' as usize], freqs[b'c' as usize]), (1, 1));
+        assert!(freqs.iter().enumerate().all(|(i, &freq)| (freq > 0) == (freq_table[i] > 0)));
+        assert!(normalize_freqs(&[0; TABLE_SIZE]).iter().all(|&freq| freq == 0));
+    }
+
+    #[test]
+    fn skewed_entries_beat_huffman_coding() {
+        // a byte taking far less than a bit is where huffman coding loses the most
+        let input: Vec<u8> = noise(200_000).iter().map(|&byte| if byte < 8 { byte } else { 0 }).collect();
+        let (decoded, bit_size, huffman_bit_size) = round_trip(&input);
+        assert_eq!(decoded, input);
+        assert!(bit_size < huffman_bit_size.unwrap() / 2);
+    }
+
+    #[test]
+    fn entries_round_trip_across_chunks() {
+        for input in [vec![], vec![b'a'], noise(CHUNK_SIZE as usize * 2 + 17)] {
+            assert_eq!(round_trip(&input).0, input);
+        }
+    }
+
+    #[test]
+    fn frequencies_that_dont_fill_the_range_are_refused() {
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_u64(0).unwrap();
+        writer.write_bits_u32(1, SYMBOL_COUNT_WIDTH).unwrap();
+        huffman::write_gamma(&mut writer, b'a' as u32 + 1).unwrap();
+        huffman::write_gamma(&mut writer, PROB_SCALE - 1).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let mut block = FileBlock::new(b"entry", Path::new(""));
+        block.original_byte_size = 1;
+        let result = RansCodec.create_decoder(&mut BitReader::new(bytes.as_slice()), &block);
+        assert!(matches!(result, Err(ZipperError::MalformedTree)));
+    }
+}
@@ -1,128 +1,435 @@
 // Joseph Prichard
 // 1/5/2023
-// File reader implementing a bit layer
+// Bit layered reader over any byte stream
 
 use std::fs::File;
-use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::io;
+use std::io::{ErrorKind, Read, Seek, SeekFrom};
 use crate::bitwise;
-use crate::block::{FileBlock};
+use crate::block::{ArchiveHeader, EntryType, FileBlock};
+use crate::charset::SIG;
+use crate::codec::METHOD_HUFFMAN;
+use crate::error::{Result, ZipperError};
+use crate::utils::is_contained_path;
 
-const BUFFER_LEN: usize = 512;
-const BUFFER_BIT_LEN: u32 = (BUFFER_LEN * 8) as u32;
+const DEFAULT_BUFFER_LEN: usize = 512;
 
-pub struct FileReader {
-    // the file stream to read from
-    file: File,
-    // a buffer storing a block from the file
-    buffer: [u8; BUFFER_LEN],
-    // the number of bytes read from the file into the buffer
+// the stream is the last field so a reader can be used through a trait object, as BitReader<dyn Read>
+pub struct BitReader<R: Read + ?Sized> {
+    // a buffer storing a block from the stream
+    buffer: Vec<u8>,
+    // the number of bytes read from the stream into the buffer
     read_size: usize,
     // the bit position of the last read in the buffer
     bit_position: u32,
     // the total number of bits read
-    read_len: u64
+    read_len: u64,
+    // the stream to read from
+    inner: R
 }
 
+// a bit reader over a file on disk
+pub type FileReader = BitReader<File>;
+
 impl FileReader {
-    pub fn new(filepath: &str) -> FileReader {
-        // open the file into memory
-        let mut file = File::open(filepath)
-            .expect("Failed to create file for new reader");
-        // read the first buffer into memory
-        let mut buffer = [0u8; BUFFER_LEN];
-        let read_size = file.read(&mut buffer)
-            .expect("Failed to read buffer for new reader");
-        // copy necessary resources into the struct
-        FileReader {
-            file,
-            buffer,
-            read_size,
+    pub fn open<P: AsRef<Path>>(filepath: P) -> io::Result<FileReader> {
+        Ok(BitReader::new(File::open(filepath)?))
+    }
+}
+
+impl<R: Read> BitReader<R> {
+    pub fn new(inner: R) -> BitReader<R> {
+        BitReader::with_capacity(DEFAULT_BUFFER_LEN, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: R) -> BitReader<R> {
+        // the first buffer is read lazily on the first read
+        BitReader {
+            buffer: vec![0u8; capacity.max(1)],
+            read_size: 0,
             bit_position: 0,
-            read_len: 0
+            read_len: 0,
+            inner
         }
     }
+}
 
-    pub fn seek_from_start(&mut self, seek_pos: u64) {
-        // seeks to location in the file for next read
-        self.file.seek(SeekFrom::Start(seek_pos))
-            .expect("Failed to seek to location in reader");
-        // force a read to override the current buffer
-        self.read_size = self.file.read(&mut self.buffer)
-            .expect("Failed to read buffer in update");
-        self.bit_position = 0;
+impl<R: Read + ?Sized> BitReader<R> {
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
     }
 
     pub fn read_len(&mut self) -> u64 {
         self.read_len
     }
 
-    pub fn eof(&mut self) -> bool {
-        // eof: if buffer pointer goes past read size or last buffer read was empty
-        (self.bit_position > (8 * self.read_size) as u32) || self.read_size == 0
+    pub fn eof(&mut self) -> io::Result<bool> {
+        // eof: if every byte in the buffer was read and the next buffer read is empty
+        self.update_buffer()?;
+        Ok(self.read_size == 0)
     }
 
-    fn update_buffer(&mut self) {
-        // at end of buffer: read a new buffer
-        if self.bit_position >= BUFFER_BIT_LEN {
-            self.read_size = self.file.read(&mut self.buffer)
-                .expect("Failed to read buffer in update");
+    fn update_buffer(&mut self) -> io::Result<()> {
+        // at end of the bytes read into the buffer: read a new buffer
+        if self.bit_position >= (8 * self.read_size) as u32 {
+            self.read_size = loop {
+                match self.inner.read(&mut self.buffer) {
+                    Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                    result => break result?
+                }
+            };
             self.bit_position = 0;
         }
+        Ok(())
     }
 
-    pub fn view_byte(&mut self) -> u8 {
-        self.update_buffer();
-        self.buffer[(self.bit_position / 8) as usize]
+    // skips any bits left in a partially read byte so the next read starts on a byte boundary
+    pub fn align_to_byte(&mut self) {
+        let skipped = (8 - self.bit_position % 8) % 8;
+        self.bit_position += skipped;
+        self.read_len += skipped as u64;
     }
 
-    pub fn read_byte(&mut self) -> u8 {
-        let byte = self.view_byte();
+    pub fn view_byte(&mut self) -> io::Result<u8> {
+        if self.eof()? {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "Failed to read past the end of stream"));
+        }
+        Ok(self.buffer[(self.bit_position / 8) as usize])
+    }
+
+    pub fn read_byte(&mut self) -> io::Result<u8> {
+        let byte = self.view_byte()?;
         self.bit_position += 8;
         self.read_len += 8;
-        byte
+        Ok(byte)
     }
 
-    pub fn read_bits(&mut self, count: u8) -> u8 {
+    pub fn read_bits(&mut self, count: u8) -> io::Result<u8> {
         // read each bit individually as they might end up in different bytes in the buffer
         let mut byte = 0;
         for i in 0..count {
-            if self.read_bit() > 0 {
+            if self.read_bit()? > 0 {
                 byte = bitwise::set_bit(byte as u32, i as u32);
             }
         }
-        byte
+        Ok(byte)
     }
 
-    pub fn read_bit(&mut self) -> u8 {
-        let byte = self.view_byte();
+    // reads a number wider than a byte, lowest bit first like read_bits
+    pub fn read_bits_u32(&mut self, count: u8) -> io::Result<u32> {
+        let mut num = 0;
+        for i in 0..count {
+            num |= (self.read_bit()? as u32) << i;
+        }
+        Ok(num)
+    }
+
+    pub fn read_bit(&mut self) -> io::Result<u8> {
+        let byte = self.view_byte()?;
         let bit = bitwise::get_bit(byte as u32, self.bit_position % 8);
         self.bit_position += 1;
         self.read_len += 1;
-        bit
+        Ok(bit)
+    }
+
+    pub fn read_archive_header(&mut self) -> Result<ArchiveHeader> {
+        // a stream too short to hold the signature isn't an archive either
+        match self.read_u64() {
+            Ok(sig) if sig == SIG => (),
+            Err(err) if err.kind() != ErrorKind::UnexpectedEof => return Err(ZipperError::Io(err)),
+            _ => return Err(ZipperError::BadSignature)
+        }
+        self.read_archive_header_fields().map_err(header_error)
+    }
+
+    fn read_archive_header_fields(&mut self) -> io::Result<ArchiveHeader> {
+        let mut header = ArchiveHeader::new();
+        header.version_major = self.read_u16()?;
+        header.version_minor = self.read_u16()?;
+        header.flags = self.read_u32()?;
+        Ok(header)
+    }
+
+    pub fn read_block(&mut self) -> Result<FileBlock> {
+        let record_len = self.read_u32().map_err(header_error)? as u64;
+        let start_read_len = self.read_len;
+        let block = self.read_block_fields(record_len).map_err(header_error)?;
+        // a record can't be shorter than its fields, but it can end with fields from a newer minor version
+        let fields_len = (self.read_len - start_read_len) / 8;
+        if fields_len > record_len {
+            return Err(ZipperError::TruncatedHeader);
+        }
+        self.skip_bytes(record_len - fields_len).map_err(header_error)?;
+        Ok(block)
+    }
+
+    fn read_block_fields(&mut self, record_len: u64) -> io::Result<FileBlock> {
+        let start_read_len = self.read_len;
+        // reads the name as raw bytes
+        let filename_rel = self.read_record_bytes(record_len)?;
+        let mut block = FileBlock::new(&filename_rel, Path::new(""));
+        // names are joined onto the extraction directory, so they can't be empty, absolute, or climb out of it
+        if !is_contained_path(&block.filename_rel_path()) {
+            return Err(io::Error::new(ErrorKind::InvalidData, ZipperError::UnsafePath(block.display_name().into_owned())));
+        }
+        block.tree_bit_size = self.read_u64()?;
+        block.data_bit_size = self.read_u64()?;
+        block.file_byte_offset = self.read_u64()?;
+        block.original_byte_size = self.read_u64()?;
+        block.checksum = self.read_u32()?;
+        // read the file metadata
+        block.mode = self.read_u32()?;
+        block.mtime = self.read_u64()? as i64;
+        block.mtime_nsec = self.read_u32()?;
+        // nanoseconds past the second can't add up to another whole second
+        if block.mtime_nsec >= 1_000_000_000 {
+            return Err(io::Error::new(ErrorKind::InvalidData, "Modification time nanoseconds are out of range"));
+        }
+        block.uid = self.read_u32()?;
+        block.gid = self.read_u32()?;
+        // read the entry type and the link target as raw bytes
+        let entry_type = self.read_byte()?;
+        block.entry_type = EntryType::from_u8(entry_type)
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidData, ZipperError::UnknownEntryType(entry_type)))?;
+        block.link_target = self.read_record_bytes(record_len)?;
+        // hard links are made to the target joined onto the extraction directory, like the names
+        if block.entry_type == EntryType::HardLink && !is_contained_path(&block.link_target_path()) {
+            return Err(io::Error::new(ErrorKind::InvalidData, ZipperError::UnsafePath(block.display_entry())));
+        }
+        // records written before version 3.1 were always huffman coded
+        let fields_len = (self.read_len - start_read_len) / 8;
+        if fields_len < record_len {
+            block.method = self.read_byte()?;
+        } else {
+            block.method = METHOD_HUFFMAN;
+        }
+        // records written before version 3.2 start their data on a byte
+        let fields_len = (self.read_len - start_read_len) / 8;
+        if fields_len < record_len {
+            block.bit_offset = self.read_byte()?;
+            if block.bit_offset >= 8 {
+                return Err(io::Error::new(ErrorKind::InvalidData, "Bit offset is past the end of a byte"));
+            }
+        }
+        // records written before version 3.3 are coded with the first code table
+        let fields_len = (self.read_len - start_read_len) / 8;
+        if fields_len < record_len {
+            block.code_book = self.read_u16()?;
+        }
+        Ok(block)
+    }
+
+    // reads bytes prefixed by their length, checking the length fits in the record before allocating
+    fn read_record_bytes(&mut self, record_len: u64) -> io::Result<Vec<u8>> {
+        let len = self.read_u32()? as u64;
+        if len > record_len {
+            return Err(io::Error::new(ErrorKind::UnexpectedEof, "Field is longer than its record"));
+        }
+        self.read_bytes(len as usize)
     }
 
-    pub fn read_block(&mut self) -> FileBlock {
-        // reads string as bytes from file
-        let mut filename_rel = String::new();
-        let mut byte = self.read_byte();
-        while byte != 0 {
-            filename_rel.push(byte as char);
-            byte = self.read_byte();
+    pub fn read_bytes(&mut self, len: usize) -> io::Result<Vec<u8>> {
+        let mut bytes = Vec::with_capacity(len);
+        for _ in 0..len {
+            bytes.push(self.read_byte()?);
         }
-        // create block and read u64 values from file into fields
-        let mut block = FileBlock::new(&filename_rel, "");
-        block.tree_bit_size = self.read_u64();
-        block.data_bit_size = self.read_u64();
-        block.file_byte_offset = self.read_u64();
-        block.original_byte_size = self.read_u64();
-        block
+        Ok(bytes)
     }
 
-    pub fn read_u64(&mut self) -> u64 {
+    pub fn skip_bytes(&mut self, len: u64) -> io::Result<()> {
+        for _ in 0..len {
+            self.read_byte()?;
+        }
+        Ok(())
+    }
+
+    pub fn read_u16(&mut self) -> io::Result<u16> {
+        let mut buffer = [0u8; 2];
+        for byte in &mut buffer {
+            *byte = self.read_byte()?;
+        }
+        Ok(u16::from_le_bytes(buffer))
+    }
+
+    pub fn read_u32(&mut self) -> io::Result<u32> {
+        let mut buffer = [0u8; 4];
+        for byte in &mut buffer {
+            *byte = self.read_byte()?;
+        }
+        Ok(u32::from_le_bytes(buffer))
+    }
+
+    pub fn read_u64(&mut self) -> io::Result<u64> {
         let mut buffer = [0u8; 8];
-        for i in 0..8 {
-            buffer[i] = self.read_byte();
+        for byte in &mut buffer {
+            *byte = self.read_byte()?;
         }
-        u64::from_le_bytes(buffer)
+        Ok(u64::from_le_bytes(buffer))
+    }
+}
+
+impl<R: Read + Seek + ?Sized> BitReader<R> {
+    pub fn seek_from_start(&mut self, seek_pos: u64) -> io::Result<()> {
+        // seeks to location in the stream for next read
+        self.inner.seek(SeekFrom::Start(seek_pos))?;
+        // discard the current buffer so the next read starts from the new location
+        self.read_size = 0;
+        self.bit_position = 0;
+        Ok(())
+    }
+}
+
+// a header that runs past the end of the stream is truncated rather than an i/o failure
+pub(crate) fn header_error(err: io::Error) -> ZipperError {
+    match err.kind() {
+        ErrorKind::UnexpectedEof => ZipperError::TruncatedHeader,
+        _ => ZipperError::from(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::write::BitWriter;
+    use super::*;
+
+    #[test]
+    fn bits_round_trip_through_buffers_of_any_size() {
+        // a one byte buffer makes every multi bit read and write cross a buffer boundary
+        let mut bytes = vec![];
+        let mut writer = BitWriter::with_capacity(1, &mut bytes);
+        writer.write_bits(0b101, 3).unwrap();
+        writer.write_bit(1).unwrap();
+        // whole bytes are written from a byte boundary
+        writer.align_to_byte();
+        writer.write_byte(0xA5).unwrap();
+        writer.write_u64(u64::MAX - 1).unwrap();
+        writer.write_bit(1).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert_eq!(bytes.len(), 11);
+
+        let mut reader = BitReader::with_capacity(1, bytes.as_slice());
+        assert_eq!(reader.read_bits(3).unwrap(), 0b101);
+        assert_eq!(reader.read_bit().unwrap(), 1);
+        reader.align_to_byte();
+        assert_eq!(reader.read_byte().unwrap(), 0xA5);
+        assert_eq!(reader.read_u64().unwrap(), u64::MAX - 1);
+        assert_eq!(reader.read_bit().unwrap(), 1);
+        reader.align_to_byte();
+        assert!(reader.eof().unwrap());
+        assert_eq!(reader.read_byte().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn fields_appended_to_a_record_are_skipped() {
+        let mut block = FileBlock::new(b"name", Path::new(""));
+        block.original_byte_size = 42;
+        block.checksum = 7;
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_block(&block).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        // pretend a newer minor version appended two bytes to the record, followed by the next byte in the stream
+        let record_len = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        bytes[..4].copy_from_slice(&(record_len + 2).to_le_bytes());
+        bytes.extend_from_slice(&[0xAA, 0xBB, 0x1D]);
+
+        let mut reader = BitReader::new(bytes.as_slice());
+        let read = reader.read_block().unwrap();
+        assert_eq!(read.filename_rel, b"name");
+        assert_eq!(read.original_byte_size, 42);
+        assert_eq!(read.checksum, 7);
+        assert_eq!(reader.read_byte().unwrap(), 0x1D);
+
+        // a record too short for the fields every 3.x record has is truncated
+        bytes[..4].copy_from_slice(&(record_len - 5).to_le_bytes());
+        assert!(matches!(BitReader::new(bytes.as_slice()).read_block(), Err(ZipperError::TruncatedHeader)));
+    }
+
+    #[test]
+    fn names_outside_the_extraction_directory_are_refused() {
+        for name in [&b"../escaped"[..], b"dir/../../escaped", b"/etc/passwd", b"", b"."] {
+            let mut bytes = vec![];
+            let mut writer = BitWriter::new(&mut bytes);
+            writer.write_block(&FileBlock::new(name, Path::new(""))).unwrap();
+            writer.flush().unwrap();
+            drop(writer);
+            assert!(matches!(BitReader::new(bytes.as_slice()).read_block(), Err(ZipperError::UnsafePath(_))));
+        }
+    }
+
+    #[test]
+    fn nanoseconds_past_a_whole_second_are_refused() {
+        let mut block = FileBlock::new(b"name", Path::new(""));
+        block.mtime = i64::MAX;
+        block.mtime_nsec = u32::MAX;
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_block(&block).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert!(BitReader::new(bytes.as_slice()).read_block().is_err());
+    }
+
+    #[test]
+    fn hard_links_outside_the_archive_are_refused() {
+        let mut block = FileBlock::new(b"link", Path::new(""));
+        block.entry_type = EntryType::HardLink;
+        block.link_target = b"../../etc/passwd".to_vec();
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_block(&block).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert!(matches!(BitReader::new(bytes.as_slice()).read_block(), Err(ZipperError::UnsafePath(_))));
+    }
+
+    #[test]
+    fn records_without_a_method_are_huffman_coded() {
+        // a 3.0 record ends before the method, the bit offset and the code table index
+        let mut block = FileBlock::new(b"name", Path::new(""));
+        block.method = 0xFF;
+        block.bit_offset = 3;
+        block.code_book = 0x0102;
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_block(&block).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        let record_len = u32::from_le_bytes(bytes[..4].try_into().unwrap());
+        let read_fields = |fields_dropped: u32| {
+            let mut bytes = bytes[..bytes.len() - fields_dropped as usize].to_vec();
+            bytes[..4].copy_from_slice(&(record_len - fields_dropped).to_le_bytes());
+            let read = BitReader::new(bytes.as_slice()).read_block().unwrap();
+            (read.method, read.bit_offset, read.code_book)
+        };
+        assert_eq!(read_fields(4), (METHOD_HUFFMAN, 0, 0));
+        // methods that aren't built in are left for the registry the archive is opened with to decode
+        assert_eq!(read_fields(3), (0xFF, 0, 0));
+        assert_eq!(read_fields(2), (0xFF, 3, 0));
+        assert_eq!(read_fields(0), (0xFF, 3, 0x0102));
+    }
+
+    #[test]
+    fn bit_offsets_past_the_end_of_a_byte_are_refused() {
+        let mut block = FileBlock::new(b"name", Path::new(""));
+        block.bit_offset = 7;
+        let mut bytes = vec![];
+        let mut writer = BitWriter::new(&mut bytes);
+        writer.write_block(&block).unwrap();
+        writer.flush().unwrap();
+        drop(writer);
+        assert_eq!(BitReader::new(bytes.as_slice()).read_block().unwrap().bit_offset, 7);
+
+        // the bit offset is followed by the u16 code table index
+        let bit_offset_index = bytes.len() - 3;
+        bytes[bit_offset_index] = 8;
+        assert!(BitReader::new(bytes.as_slice()).read_block().is_err());
     }
 }
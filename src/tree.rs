@@ -4,41 +4,73 @@
 
 use std::cmp::Ordering;
 
+// the nodes are kept in one list and refer to each other by index, so a node can be reached from its
+// children as well as its parent, and two subtrees can trade places without rebuilding either
+#[derive(Default)]
 pub struct Tree {
-    pub root: Box<Node>,
-    pub symbol_count: u32
+    pub nodes: Vec<Node>,
+    pub root: usize
 }
 
 pub struct Node {
-    pub left: Option<Box<Node>>,
-    pub right: Option<Box<Node>>,
-    pub plain_symbol: u8,
+    pub left: Option<usize>,
+    pub right: Option<usize>,
+    pub parent: Option<usize>,
+    pub plain_symbol: u16,
     pub weight: u64
 }
 
 impl Node {
     // creates a leaf structure with no children
-    pub fn leaf(symbol: u8, weight: u64) -> Node {
+    pub fn leaf(symbol: u16, weight: u64) -> Node {
         Node {
             left: None,
             right: None,
+            parent: None,
             plain_symbol: symbol,
             weight
         }
     }
 
-    // moves the left and right nodes
-    pub fn internal(left: Box<Node>, right: Box<Node>, symbol: u8, weight: u64) -> Node {
-        Node {
-            left: Some(Box::new(*left)),
-            right: Some(Box::new(*right)),
-            plain_symbol: symbol,
-            weight
-        }
+    pub fn is_leaf(&self) -> bool {
+        self.left.is_none() && self.right.is_none()
     }
+}
 
-    pub fn is_leaf(&self) -> bool {
-        self.left == None && self.right == None
+impl Tree {
+    // adds a leaf without a parent, returning its index
+    pub fn add_leaf(&mut self, symbol: u16, weight: u64) -> usize {
+        self.nodes.push(Node::leaf(symbol, weight));
+        self.nodes.len() - 1
+    }
+
+    // adds a node with the left and right nodes as its children, returning its index
+    pub fn add_internal(&mut self, left: usize, right: usize, symbol: u16, weight: u64) -> usize {
+        let index = self.add_leaf(symbol, weight);
+        self.set_children(index, left, right);
+        index
+    }
+
+    // makes the left and right nodes the children of the node at the index
+    pub fn set_children(&mut self, index: usize, left: usize, right: usize) {
+        self.nodes[index].left = Some(left);
+        self.nodes[index].right = Some(right);
+        self.nodes[left].parent = Some(index);
+        self.nodes[right].parent = Some(index);
+    }
+
+    // swaps two subtrees, neither containing the other, so each takes the other's place under its parent
+    pub fn swap_subtrees(&mut self, a: usize, b: usize) {
+        self.nodes.swap(a, b);
+        let parent = self.nodes[a].parent;
+        self.nodes[a].parent = self.nodes[b].parent;
+        self.nodes[b].parent = parent;
+        for index in [a, b] {
+            if let (Some(left), Some(right)) = (self.nodes[index].left, self.nodes[index].right) {
+                self.nodes[left].parent = Some(index);
+                self.nodes[right].parent = Some(index);
+            }
+        }
     }
 }
 
@@ -52,7 +84,7 @@ impl PartialEq<Self> for Node {
 
 impl PartialOrd<Self> for Node {
     fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        Some(self.cmp(&other))
+        Some(self.cmp(other))
     }
 }
 
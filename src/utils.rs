@@ -2,8 +2,12 @@
 // 1/5/2023
 // Utilities for files, sizes, and conversions
 
-use std::fs;
-use std::path::Path;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+#[cfg(unix)]
+use std::ffi::OsStr;
+#[cfg(unix)]
+use std::os::unix::ffi::OsStrExt;
 
 pub fn get_size_of<T>(_: T) -> usize {
     std::mem::size_of::<T>()
@@ -19,39 +23,97 @@ pub const fn str_to_u64(str: &str) -> u64 {
     u64::from_le_bytes(buffer)
 }
 
-pub fn dir_entry_size(path: &Path) -> u64 {
-    let mut size = 0;
-    if path.is_dir() {
-        for entry in fs::read_dir(path).expect("Can't read directory") {
-            let entry = entry.expect("Entry is invalid");
-            let path = entry.path();
-            size += dir_entry_size(&path);
+// whether the path names something inside whatever directory it is joined onto, having no root, prefix, or parent components
+pub fn is_contained_path(path: &Path) -> bool {
+    path.components().any(|component| matches!(component, Component::Normal(_))) &&
+        path.components().all(|component| matches!(component, Component::Normal(_) | Component::CurDir))
+}
+
+// paths are stored as their raw os bytes on unix so any file name round trips exactly
+#[cfg(unix)]
+pub fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.as_os_str().as_bytes().to_vec()
+}
+
+#[cfg(unix)]
+pub fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(OsStr::from_bytes(bytes))
+}
+
+// other platforms store paths as utf-8, which covers any name that is valid unicode
+#[cfg(not(unix))]
+pub fn path_to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().as_bytes().to_vec()
+}
+
+#[cfg(not(unix))]
+pub fn bytes_to_path(bytes: &[u8]) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(bytes).as_ref())
+}
+
+// converts a system time into whole seconds since the unix epoch and the nanoseconds after them
+#[cfg(not(unix))]
+pub fn system_time_to_mtime(time: SystemTime) -> (i64, u32) {
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(since) => (since.as_secs() as i64, since.subsec_nanos()),
+        Err(err) => {
+            // times before the epoch round down to the previous second
+            let before = err.duration();
+            let secs = -(before.as_secs() as i64);
+            match before.subsec_nanos() {
+                0 => (secs, 0),
+                nanos => (secs - 1, 1_000_000_000 - nanos)
+            }
         }
-    } else {
-        size += path.metadata().expect("Can't get metadata").len();
     }
-    size
 }
 
-pub fn get_dir_name(dir: &str) -> &str {
-    Path::new(dir)
-        .file_name()
-        .expect("Couldn't get directory name")
-        .to_str()
-        .expect("Couldn't convert to string")
+// converts seconds and nanoseconds since the unix epoch back into a system time, if the platform can represent it
+pub fn mtime_to_system_time(mtime: i64, mtime_nsec: u32) -> Option<SystemTime> {
+    let secs = Duration::from_secs(mtime.unsigned_abs());
+    let time = if mtime >= 0 {
+        UNIX_EPOCH.checked_add(secs)?
+    } else {
+        UNIX_EPOCH.checked_sub(secs)?
+    };
+    time.checked_add(Duration::from_nanos(mtime_nsec as u64))
 }
 
-pub fn get_parent_name(dir: &str) -> &str {
-     Path::new(dir)
-        .parent()
-        .expect("Couldn't get parent")
-        .to_str()
-        .expect("Couldn't convert to string")
+// formats seconds since the unix epoch as a utc date and time
+pub fn format_timestamp(secs: i64) -> String {
+    let days = secs.div_euclid(86400);
+    let time = secs.rem_euclid(86400);
+    // convert days to a civil date, with years starting in march so leap days fall at the end
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}", year, month, day, time / 3600, time % 3600 / 60, time % 60)
 }
 
-pub fn get_no_ext(path: &str) -> String {
-    Path::new(path)
-        .with_extension("")
-        .display()
-        .to_string()
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mtimes_convert_to_system_times() {
+        assert_eq!(mtime_to_system_time(0, 0), Some(UNIX_EPOCH));
+        assert_eq!(mtime_to_system_time(1, 500), Some(UNIX_EPOCH + Duration::new(1, 500)));
+        // times before the epoch count their nanoseconds forward from the earlier second
+        assert_eq!(mtime_to_system_time(-2, 250), Some(UNIX_EPOCH - Duration::from_secs(2) + Duration::from_nanos(250)));
+        // times past the latest one the platform can represent aren't converted rather than panicking
+        assert_eq!(mtime_to_system_time(i64::MAX, u32::MAX), None);
+    }
+
+    #[test]
+    fn timestamps_format_as_utc_dates() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00:00");
+        assert_eq!(format_timestamp(951_827_696), "2000-02-29 12:34:56");
+        assert_eq!(format_timestamp(-1), "1969-12-31 23:59:59");
+    }
+}
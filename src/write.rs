@@ -1,75 +1,148 @@
 // Joseph Prichard
 // 1/5/2023
-// File writer implementing a bit layer
+// Bit layered writer over any byte stream
 
 use std::fs::{File, OpenOptions};
+use std::path::Path;
+use std::io;
 use std::io::{Write};
-use std::mem;
 use crate::bitwise;
 use crate::bitwise::{get_bit, SymbolCode};
-use crate::block::{FileBlock};
+use crate::block::{ArchiveHeader, FileBlock};
+use crate::charset::SIG;
 
-const BUFFER_LEN: usize = 512;
-const BUFFER_BIT_LEN: u32 = (BUFFER_LEN * 8) as u32;
+const DEFAULT_BUFFER_LEN: usize = 512;
 
-pub struct FileWriter {
-    // the file stream to write to
-    file: File,
-    // a buffer storing a block to be written to the file
-    buffer: [u8; BUFFER_LEN],
+// the stream is the last field so a writer can be used through a trait object, as BitWriter<dyn Write>
+pub struct BitWriter<W: Write + ?Sized> {
+    // a buffer storing a block to be written to the stream
+    buffer: Vec<u8>,
     // the bit position of the last write in the buffer
-    bit_position: u32
+    bit_position: u32,
+    // the total number of bits written
+    write_len: u64,
+    // the stream to write to
+    inner: W
 }
 
+// a bit writer over a file on disk
+pub type FileWriter = BitWriter<File>;
+
 impl FileWriter {
-    pub fn new(filepath: &str) -> FileWriter {
-        FileWriter {
-            file: OpenOptions::new()
-                .write(true)
-                .append(false)
-                .create(true)
-                .open(filepath)
-                .expect("Failed to open file for new writer"),
-            buffer: [0u8; BUFFER_LEN],
-            bit_position: 0
+    pub fn create<P: AsRef<Path>>(filepath: P) -> io::Result<FileWriter> {
+        let file = OpenOptions::new()
+            .write(true)
+            .append(false)
+            .create(true)
+            .truncate(true)
+            .open(filepath)?;
+        Ok(BitWriter::new(file))
+    }
+}
+
+impl<W: Write> BitWriter<W> {
+    pub fn new(inner: W) -> BitWriter<W> {
+        BitWriter::with_capacity(DEFAULT_BUFFER_LEN, inner)
+    }
+
+    pub fn with_capacity(capacity: usize, inner: W) -> BitWriter<W> {
+        BitWriter {
+            buffer: vec![0u8; capacity.max(1)],
+            bit_position: 0,
+            write_len: 0,
+            inner
         }
     }
+}
+
+impl<W: Write + ?Sized> BitWriter<W> {
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
 
-    fn persist_buffer(&mut self) {
-        self.file.write(&self.buffer[0..((self.bit_position / 8) as usize)])
-            .expect("Failed to persist buffer to file");
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
     }
 
-    fn update_buffer(&mut self) {
+    pub fn write_len(&self) -> u64 {
+        self.write_len
+    }
+
+    fn persist_buffer(&mut self) -> io::Result<()> {
+        self.inner.write_all(&self.buffer[0..((self.bit_position / 8) as usize)])
+    }
+
+    fn update_buffer(&mut self) -> io::Result<()> {
         // check if at end of buffer: persist current buffer and start writing on a new one
-        if self.bit_position >= BUFFER_BIT_LEN {
-            self.persist_buffer();
+        if self.bit_position >= (8 * self.buffer.len()) as u32 {
+            self.persist_buffer()?;
             self.bit_position = 0;
-            self.buffer = [0u8; BUFFER_LEN];
+            self.buffer.fill(0);
         }
+        Ok(())
+    }
+
+    // pads the last byte and persists everything written so far to the stream
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.align_to_byte();
+        self.persist_buffer()?;
+        self.bit_position = 0;
+        self.buffer.fill(0);
+        self.inner.flush()
+    }
+
+    // persists every whole byte written so far, keeping the bits of a partly written byte in the buffer
+    pub fn flush_whole_bytes(&mut self) -> io::Result<()> {
+        self.update_buffer()?;
+        self.persist_buffer()?;
+        let partial_byte = self.buffer[(self.bit_position / 8) as usize];
+        self.buffer.fill(0);
+        self.buffer[0] = partial_byte;
+        self.bit_position %= 8;
+        self.inner.flush()
     }
 
     pub fn align_to_byte(&mut self) {
-        self.bit_position = ((self.bit_position + 7) / 8) * 8;
+        let padding = (8 - self.bit_position % 8) % 8;
+        self.bit_position += padding;
+        self.write_len += padding as u64;
     }
 
-    pub fn write_byte(&mut self, byte: u8) {
-        self.update_buffer();
+    pub fn write_byte(&mut self, byte: u8) -> io::Result<()> {
+        self.update_buffer()?;
 
         // write the byte directly into the buffer
         self.buffer[(self.bit_position / 8) as usize] = byte;
         self.bit_position += 8;
+        self.write_len += 8;
+        Ok(())
     }
 
-    pub fn write_bits(&mut self, byte: u8, count: u8) {
+    pub fn write_bytes(&mut self, bytes: &[u8]) -> io::Result<()> {
+        for &byte in bytes {
+            self.write_byte(byte)?;
+        }
+        Ok(())
+    }
+
+    pub fn write_bits(&mut self, byte: u8, count: u8) -> io::Result<()> {
         // write each bit individually as they might end up in different bytes in the buffer
         for i in 0..count {
-            self.write_bit(get_bit(byte as u32, i as u32));
+            self.write_bit(get_bit(byte as u32, i as u32))?;
         }
+        Ok(())
     }
 
-    pub fn write_bit(&mut self, bit: u8) {
-        self.update_buffer();
+    // writes the low bits of a number wider than a byte, lowest bit first like write_bits
+    pub fn write_bits_u32(&mut self, num: u32, count: u8) -> io::Result<()> {
+        for i in 0..count {
+            self.write_bit(get_bit(num, i as u32))?;
+        }
+        Ok(())
+    }
+
+    pub fn write_bit(&mut self, bit: u8) -> io::Result<()> {
+        self.update_buffer()?;
 
         // write the bit back into the buffer
         if bit > 0 {
@@ -78,37 +151,67 @@ impl FileWriter {
         }
 
         self.bit_position += 1;
+        self.write_len += 1;
+        Ok(())
     }
 
-    pub fn write_symbol(&mut self, symbol: &SymbolCode) {
+    pub fn write_symbol(&mut self, symbol: &SymbolCode) -> io::Result<()> {
         for i in 0..symbol.bit_len {
-            self.write_bit(get_bit(symbol.encoded_symbol, i as u32));
+            self.write_bit(get_bit(symbol.encoded_symbol, i as u32))?;
         }
+        Ok(())
     }
 
-    pub fn write_block(&mut self, block: &FileBlock) {
-        // write string with a null terminator at the end
-        for c in block.filename_rel.chars() {
-            self.write_byte(c as u8);
-        }
-        self.write_byte(0);
-        // write each u64 field into the file
-        self.write_u64(block.tree_bit_size);
-        self.write_u64(block.data_bit_size);
-        self.write_u64(block.file_byte_offset);
-        self.write_u64(block.original_byte_size);
-    }
-
-    pub fn write_u64(&mut self, num: u64) {
-        let buffer: [u8; 8] = unsafe { mem::transmute(num) };
-        for i in 0..8 {
-            self.write_byte(buffer[i]);
-        }
+    pub fn write_archive_header(&mut self, header: &ArchiveHeader) -> io::Result<()> {
+        self.write_u64(SIG)?;
+        self.write_u16(header.version_major)?;
+        self.write_u16(header.version_minor)?;
+        self.write_u32(header.flags)
+    }
+
+    pub fn write_block(&mut self, block: &FileBlock) -> io::Result<()> {
+        // write the length of the record after the length field itself
+        self.write_u32((block.get_header_size() - 4) as u32)?;
+        // write the name as raw bytes prefixed with its length
+        self.write_u32(block.filename_rel.len() as u32)?;
+        self.write_bytes(&block.filename_rel)?;
+        // write each u64 field into the stream
+        self.write_u64(block.tree_bit_size)?;
+        self.write_u64(block.data_bit_size)?;
+        self.write_u64(block.file_byte_offset)?;
+        self.write_u64(block.original_byte_size)?;
+        self.write_u32(block.checksum)?;
+        // write the file metadata
+        self.write_u32(block.mode)?;
+        self.write_u64(block.mtime as u64)?;
+        self.write_u32(block.mtime_nsec)?;
+        self.write_u32(block.uid)?;
+        self.write_u32(block.gid)?;
+        // write the entry type and the link target prefixed with its length
+        self.write_byte(block.entry_type as u8)?;
+        self.write_u32(block.link_target.len() as u32)?;
+        self.write_bytes(&block.link_target)?;
+        self.write_byte(block.method)?;
+        self.write_byte(block.bit_offset)?;
+        self.write_u16(block.code_book)
+    }
+
+    pub fn write_u16(&mut self, num: u16) -> io::Result<()> {
+        self.write_bytes(&num.to_le_bytes())
+    }
+
+    pub fn write_u32(&mut self, num: u32) -> io::Result<()> {
+        self.write_bytes(&num.to_le_bytes())
+    }
+
+    pub fn write_u64(&mut self, num: u64) -> io::Result<()> {
+        self.write_bytes(&num.to_le_bytes())
     }
 }
 
-impl Drop for FileWriter {
+impl<W: Write + ?Sized> Drop for BitWriter<W> {
     fn drop(&mut self) {
-        self.persist_buffer();
+        // errors can't be reported from drop, callers should flush to observe them
+        let _ = self.flush();
     }
-}
\ No newline at end of file
+}